@@ -4,7 +4,7 @@ extern crate axp209;
 
 use linux_hal::{I2cdev};
 use linux_hal::i2cdev::linux::LinuxI2CError;
-use axp209::{Axp209, BATTERY_LEVEL_MISSING};
+use axp209::{Axp209, Error, BATTERY_LEVEL_MISSING};
 
 fn main() {
     let i2c = I2cdev::new("/dev/i2c-0").unwrap();
@@ -14,38 +14,41 @@ fn main() {
     display_battery_info(level);
 
     let voltage = pmic.battery_voltage().unwrap();
-    println!("Voltage: {}mV", voltage);
+    println!("Voltage: {}", voltage);
 
     let value = pmic.battery_discharging_current().unwrap();
-    println!("Discharge Current: {}mA", value);
+    println!("Discharge Current: {}", value);
 
     let value = pmic.battery_charging_current().unwrap();
-    println!("Charge Current:    {}mA", value);
+    println!("Charge Current:    {}", value);
 
     let value = pmic.acin_voltage().unwrap();
-    println!("ACIN Voltage:      {}mV", value);
+    println!("ACIN Voltage:      {}", value);
 
     let value = pmic.acin_current().unwrap();
-    println!("ACIN Current:      {}mA", value);
+    println!("ACIN Current:      {}", value);
 
     let value = pmic.vbus_voltage().unwrap();
-    println!("Vbus Voltage:      {}mV", value);
+    println!("Vbus Voltage:      {}", value);
 
     let value = pmic.vbus_current().unwrap();
-    println!("Vbus Current:      {}mA", value);
+    println!("Vbus Current:      {}", value);
 
     let value = pmic.temperature().unwrap();
-    println!("Temperature:       {}°C", value);
+    println!("Temperature:       {}", value);
 
     let value = pmic.ts_voltage().unwrap();
-    println!("Temp Sensor Pin:   {}mV", value);
+    println!("Temp Sensor Pin:   {}", value);
 
     let value = pmic.ipsout_voltage().unwrap();
-    println!("Ipsout?!:          {}mV", value);
+    println!("Ipsout?!:          {}", value);
 
     println!("");
 
     let value = pmic.adc_control().unwrap();
+    println!("ADC Control Flags: {}", value);
+
+    let value = pmic.power_control().unwrap();
     println!("Power Control Flags: {:?}", value);
 
     let value = pmic.power_status().unwrap();
@@ -60,7 +63,7 @@ fn main() {
     println!("Timer:\n\tExpired: {}\n\tTime (minutes): {}", value.expired(), value.minutes());
 }
 
-fn display_battery_info(level: Result<u8, LinuxI2CError>) {
+fn display_battery_info(level: Result<u8, Error<LinuxI2CError>>) {
     let level = match level {
         Ok(x) => x,
         _ => { 
@@ -1,6 +1,12 @@
 // TODO: Is this much wrapping actually worthwhile? What is the expectation? Check other crates.
 
+use core::fmt;
+
+use channel::Channel;
+
 bitflags! {
+    /// Registers 0x82 (high byte) and 0x83 (low byte) switch each ADC
+    /// channel on and off. Bits 6 to 4 and 1 to 0 of 0x83 are reserved.
     pub struct AdcControl: u16 {
         /// Register address to read battery voltage from
         const BATTERY_VOLTAGE = 1 << 15;
@@ -28,13 +34,55 @@ bitflags! {
     }
 }
 
+/// Names used when printing, in register order. They match the pin
+/// names in the datasheet, which is why `TS_FUNCTION` shows up as `TS`.
+const FLAG_NAMES: [(AdcControl, &str); 11] = [
+    (AdcControl::BATTERY_VOLTAGE, "BATTERY_VOLTAGE"),
+    (AdcControl::BATTERY_CURRENT, "BATTERY_CURRENT"),
+    (AdcControl::ACIN_VOLTAGE, "ACIN_VOLTAGE"),
+    (AdcControl::ACIN_CURRENT, "ACIN_CURRENT"),
+    (AdcControl::VBUS_VOLTAGE, "VBUS_VOLTAGE"),
+    (AdcControl::VBUS_CURRENT, "VBUS_CURRENT"),
+    (AdcControl::APS_VOLTAGE, "APS_VOLTAGE"),
+    (AdcControl::TS_FUNCTION, "TS"),
+    (AdcControl::TEMPERATURE, "TEMPERATURE"),
+    (AdcControl::GPIO0, "GPIO0"),
+    (AdcControl::GPIO1, "GPIO1"),
+];
+
 impl AdcControl {
+    /// No checks are made here, and it's expected that it be populated by
+    /// the raw value from the axp209 chip.
     pub fn new(value: u16) -> Self {
         Self {
             bits: value,
         }
     }
 
+    /// Everything needed to read the given channels, and nothing else
+    pub fn from_channels(channels: &[Channel]) -> Self {
+        channels.iter().fold(Self::empty(), |control, channel| control.with(*channel))
+    }
+
+    /// Add what's needed to read a channel. Handy for building up a set:
+    ///
+    /// ```
+    /// # use axp209::{AdcControl, Channel};
+    ///     let control = AdcControl::empty()
+    ///         .with(Channel::BatteryVoltage)
+    ///         .with(Channel::BatteryDischargeCurrent)
+    ///         .with(Channel::Temperature);
+    /// ```
+    pub fn with(mut self, channel: Channel) -> Self {
+        self.insert(channel.info().enable);
+        self
+    }
+
+    /// Whether a channel can be read
+    pub fn is_enabled(&self, channel: Channel) -> bool {
+        self.contains(channel.info().enable)
+    }
+
     pub fn battery_voltage(&self) -> bool {
         self.contains(Self::BATTERY_VOLTAGE)
     }
@@ -75,6 +123,30 @@ impl AdcControl {
         self.set(Self::VBUS_VOLTAGE, value);
     }
 
+    pub fn vbus_current(&self) -> bool {
+        self.contains(Self::VBUS_CURRENT)
+    }
+
+    pub fn set_vbus_current(&mut self, value: bool) {
+        self.set(Self::VBUS_CURRENT, value);
+    }
+
+    pub fn aps_voltage(&self) -> bool {
+        self.contains(Self::APS_VOLTAGE)
+    }
+
+    pub fn set_aps_voltage(&mut self, value: bool) {
+        self.set(Self::APS_VOLTAGE, value);
+    }
+
+    pub fn ts_function(&self) -> bool {
+        self.contains(Self::TS_FUNCTION)
+    }
+
+    pub fn set_ts_function(&mut self, value: bool) {
+        self.set(Self::TS_FUNCTION, value);
+    }
+
     pub fn temperature(&self) -> bool {
         self.contains(Self::TEMPERATURE)
     }
@@ -98,4 +170,129 @@ impl AdcControl {
     pub fn set_gpio1(&mut self, value: bool) {
         self.set(Self::GPIO1, value);
     }
-}
\ No newline at end of file
+}
+
+/// Prints the enabled channels like `BATTERY_VOLTAGE | TS | TEMPERATURE`
+impl fmt::Display for AdcControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+
+        for &(flag, name) in FLAG_NAMES.iter() {
+            if self.contains(flag) {
+                if !first {
+                    f.write_str(" | ")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+
+        if first {
+            f.write_str("(empty)")?;
+        }
+
+        Ok(())
+    }
+}
+
+
+/// How often the ADC takes a sample of each enabled channel
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SampleRate {
+    Hz25 = 0,
+    Hz50 = 1,
+    Hz100 = 2,
+    Hz200 = 3,
+}
+
+impl SampleRate {
+    /// How long one sample takes, in milliseconds
+    pub fn period_ms(&self) -> u16 {
+        match *self {
+            SampleRate::Hz25 => 40,
+            SampleRate::Hz50 => 20,
+            SampleRate::Hz100 => 10,
+            SampleRate::Hz200 => 5,
+        }
+    }
+}
+
+/// How much current the TS pin pushes out to drive a thermistor
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TsCurrent {
+    Ua20 = 0,
+    Ua40 = 1,
+    Ua60 = 2,
+    Ua80 = 3,
+}
+
+/// When the TS pin pushes its current out
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TsCurrentMode {
+    Off = 0,
+    /// Only while the battery is charging
+    Charging = 1,
+    /// Only while the ADC is taking a sample, to save power
+    Sampling = 2,
+    Always = 3,
+}
+
+bitflags! {
+    /// Register 0x84 sets the ADC sample rate and how the TS pin behaves.
+    /// Most of it is made of multi-bit fields, so use the methods rather
+    /// than the raw bits.
+    pub struct AdcSampleTs: u8 {
+        /// Use the TS pin as a general purpose ADC input rather than for
+        /// monitoring the battery temperature
+        const TS_ADC_INPUT = 1 << 2;
+    }
+}
+
+impl AdcSampleTs {
+    /// No checks are made here, and it's expected that it be populated by
+    /// the raw value from the axp209 chip.
+    pub fn new(value: u8) -> Self {
+        Self {
+            bits: value
+        }
+    }
+
+    pub fn sample_rate(&self) -> SampleRate {
+        match self.bits >> 6 {
+            0 => SampleRate::Hz25,
+            1 => SampleRate::Hz50,
+            2 => SampleRate::Hz100,
+            _ => SampleRate::Hz200,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, value: SampleRate) {
+        self.bits = (self.bits & 0b0011_1111) | (value as u8) << 6;
+    }
+
+    pub fn ts_current(&self) -> TsCurrent {
+        match (self.bits >> 4) & 0b11 {
+            0 => TsCurrent::Ua20,
+            1 => TsCurrent::Ua40,
+            2 => TsCurrent::Ua60,
+            _ => TsCurrent::Ua80,
+        }
+    }
+
+    pub fn set_ts_current(&mut self, value: TsCurrent) {
+        self.bits = (self.bits & 0b1100_1111) | (value as u8) << 4;
+    }
+
+    pub fn ts_current_mode(&self) -> TsCurrentMode {
+        match self.bits & 0b11 {
+            0 => TsCurrentMode::Off,
+            1 => TsCurrentMode::Charging,
+            2 => TsCurrentMode::Sampling,
+            _ => TsCurrentMode::Always,
+        }
+    }
+
+    pub fn set_ts_current_mode(&mut self, value: TsCurrentMode) {
+        self.bits = (self.bits & 0b1111_1100) | value as u8;
+    }
+}
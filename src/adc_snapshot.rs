@@ -0,0 +1,70 @@
+//! All of the ADC result registers sit next to each other (0x56 - 0x7F), so
+//! rather than asking for each one separately `Axp209::adc_snapshot()` reads
+//! the whole block in a single transfer. Channels that are switched off in
+//! `AdcControl` come back as `None` instead of whatever stale value happens
+//! to be sitting in the register.
+
+use adc_control::AdcControl;
+use channel::{Channel, CHANNELS};
+use units::{DeciCelsius, MicroAmps, MicroVolts, MicroWatts, MilliAmps, MilliVolts};
+
+/// Where the block of ADC result registers starts
+const ADC_BLOCK_START: u8 = CHANNELS[0].register;
+/// Number of bytes from 0x56 up to and including 0x7F
+pub const ADC_BLOCK_SIZE: usize = 0x80 - ADC_BLOCK_START as usize;
+
+/// Converted ADC values, all sampled at the same time
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct AdcSnapshot {
+    pub battery_voltage: Option<MilliVolts>,
+    pub battery_charging_current: Option<MilliAmps>,
+    pub battery_discharging_current: Option<MilliAmps>,
+    pub acin_voltage: Option<MilliVolts>,
+    pub acin_current: Option<MilliAmps>,
+    pub vbus_voltage: Option<MilliVolts>,
+    pub vbus_current: Option<MilliAmps>,
+    pub temperature: Option<DeciCelsius>,
+    pub ts_voltage: Option<MilliVolts>,
+    pub ipsout_voltage: Option<MilliVolts>,
+    /// Unconfirmed
+    pub gpio0_voltage: Option<MilliVolts>,
+    /// Unconfirmed
+    pub gpio1_voltage: Option<MilliVolts>,
+    /// Needs both `BATTERY_VOLTAGE` and `BATTERY_CURRENT`
+    pub battery_power: Option<MicroWatts>,
+}
+
+impl AdcSnapshot {
+    /// Build a snapshot from the raw register block. `control` decides which
+    /// values are worth keeping.
+    pub fn new(block: &[u8; ADC_BLOCK_SIZE], control: AdcControl) -> Self {
+        let value = |channel: Channel| {
+            let info = channel.info();
+
+            if control.contains(info.enable) {
+                let start = (info.register - ADC_BLOCK_START) as usize;
+                Some(info.value(info.decode(&block[start..])))
+            } else {
+                None
+            }
+        };
+        let volts = |channel| value(channel).map(|x| MicroVolts(x as u32).into());
+        let amps = |channel| value(channel).map(|x| MicroAmps(x as u32).into());
+
+        AdcSnapshot {
+            battery_voltage: volts(Channel::BatteryVoltage),
+            battery_charging_current: amps(Channel::BatteryChargeCurrent),
+            battery_discharging_current: amps(Channel::BatteryDischargeCurrent),
+            acin_voltage: volts(Channel::AcinVoltage),
+            acin_current: amps(Channel::AcinCurrent),
+            vbus_voltage: volts(Channel::VbusVoltage),
+            vbus_current: amps(Channel::VbusCurrent),
+            temperature: value(Channel::Temperature).map(|x| DeciCelsius(x as i16)),
+            ts_voltage: volts(Channel::TsVoltage),
+            ipsout_voltage: volts(Channel::IpsoutVoltage),
+            gpio0_voltage: volts(Channel::Gpio0Voltage),
+            gpio1_voltage: volts(Channel::Gpio1Voltage),
+            battery_power: value(Channel::BatteryPower).map(|x| MicroWatts(x as u32)),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! One answer to "what is the battery doing?", pulled together from the
+//! Power Status, Charging Status, Charge Control and battery level
+//! registers. See `Axp209::battery_state()`.
+
+use charge_control::ChargeControl;
+use charging_status::ChargingStatus;
+use power_status::PowerStatus;
+use BATTERY_LEVEL_MISSING;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BatteryState {
+    /// There's no battery connected
+    Absent,
+    /// The battery is being charged
+    Charging,
+    /// The battery is powering the system
+    Discharging,
+    /// The charger is done and the battery is topped up
+    Full,
+    /// There's power coming in but the battery isn't charging, because the
+    /// charger is switched off or is waiting to start another cycle
+    NotCharging,
+    /// The chip is overtemperature, so charging has been stopped
+    Fault,
+    /// A deeply discharged cell is being slowly woken up
+    ActivationMode,
+}
+
+impl BatteryState {
+    /// Work out the state from the raw register values. Checks go from the
+    /// most to the least serious, so a battery that's missing is never
+    /// reported as charging, and so on.
+    pub fn new(power: PowerStatus, charging: ChargingStatus, charge: ChargeControl, level: u8) -> Self {
+        let input = power.intersects(PowerStatus::ACIN_USABLE | PowerStatus::VBUS_USABLE);
+
+        if !charging.contains(ChargingStatus::BATTERY_PRESENT) || level == BATTERY_LEVEL_MISSING {
+            BatteryState::Absent
+        } else if charging.contains(ChargingStatus::OVERTEMPERATURE) {
+            BatteryState::Fault
+        } else if charging.contains(ChargingStatus::CELL_ACTIVATION_MODE) {
+            BatteryState::ActivationMode
+        } else if charging.contains(ChargingStatus::CHARGING) {
+            BatteryState::Charging
+        } else if !input {
+            // Powering the system, however full it is
+            BatteryState::Discharging
+        } else if level >= 100 && charge.contains(ChargeControl::ENABLE) {
+            BatteryState::Full
+        } else {
+            BatteryState::NotCharging
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! Time to empty and time to full estimates, as shown in pretty much every
+//! battery powered UI. `Axp209::update_battery_times()` takes a reading and
+//! works them out, and `Axp209::time_to_empty()` and
+//! `Axp209::time_to_full()` hand back what it came up with as often as you
+//! like.
+//!
+//! The chip doesn't know how big the battery is, so these only work once
+//! `Axp209::set_battery_capacity()` has been called. The current going in
+//! or out is smoothed over several readings so the estimate doesn't jump
+//! around every time the WiFi chip wakes up, which means the first few
+//! estimates after a big change in load will lag behind a bit. Charging is
+//! assumed to carry on at the same current right to the end, which isn't
+//! quite true as the charger tapers off, so expect time to full to be a
+//! little optimistic.
+
+use core::time::Duration;
+
+use units::{MilliAmpHours, MilliAmps};
+
+/// Each new reading counts for one part in this many of the average
+const SMOOTHING: i32 = 8;
+
+/// Both estimates, worked out from the same reading
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct BatteryTimes {
+    pub to_empty: Option<Duration>,
+    pub to_full: Option<Duration>,
+}
+
+/// A moving average of the battery current, in milliamps. Positive means
+/// charging.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CurrentAverage {
+    /// `SMOOTHING` times the average. Keeping the sum rather than the average
+    /// itself means the rounding doesn't get thrown away every update, so it
+    /// settles on a steady current rather than stopping a few mA short.
+    sum: Option<i32>,
+}
+
+impl CurrentAverage {
+    /// Add a reading and get the new average
+    pub fn update(&mut self, charging: MilliAmps, discharging: MilliAmps) -> i32 {
+        let current = charging.0 as i32 - discharging.0 as i32;
+        let sum = match self.sum {
+            Some(x) => x + current - x / SMOOTHING,
+            None => current * SMOOTHING,
+        };
+
+        self.sum = Some(sum);
+        sum / SMOOTHING
+    }
+
+    /// Forget everything, for when the load is known to have changed
+    pub fn reset(&mut self) {
+        self.sum = None;
+    }
+}
+
+/// How long until the battery is empty, if it's discharging at all.
+/// `level` is in percent.
+pub fn time_to_empty(level: u8, capacity: MilliAmpHours, average: i32) -> Option<Duration> {
+    if average >= 0 {
+        return None;
+    }
+
+    let remaining = capacity.0 as u64 * level.min(100) as u64 / 100;
+
+    Some(hours(remaining, (-average) as u64))
+}
+
+/// How long until the battery is full, if it's charging at all. The
+/// current used is capped at what the charger is set to, which filters
+/// out the odd spike.
+pub fn time_to_full(level: u8, capacity: MilliAmpHours, average: i32,
+                    limit: MilliAmps) -> Option<Duration> {
+    if average <= 0 {
+        return None;
+    }
+
+    let missing = capacity.0 as u64 * (100 - level.min(100)) as u64 / 100;
+    let current = (average as u64).min(limit.0 as u64);
+
+    Some(hours(missing, current))
+}
+
+/// Milliamp hours over milliamps, as a duration
+fn hours(charge: u64, current: u64) -> Duration {
+    Duration::from_secs(charge * 3600 / current)
+}
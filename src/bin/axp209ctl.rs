@@ -0,0 +1,568 @@
+//! `axp209ctl`, for looking at and changing an AXP209 from a shell on a
+//! Linux board, without writing any Rust. Build it with the `cli` feature:
+//!
+//! ```text
+//! cargo build --release --features cli --bin axp209ctl
+//! ```
+//!
+//! Everything it prints can be had as JSON with `--json` instead, one
+//! object per line, for scripts to pick apart.
+//!
+//! With `--board` the board's rail policy applies, so it won't switch off
+//! the CPU or set a rail outside what the board can take unless `--force`
+//! is given as well.
+
+extern crate axp209;
+extern crate linux_embedded_hal as linux_hal;
+
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use axp209::{Axp209, BatteryState, Board, Channel, Error, Irq, Rail, Reading, RegisterDump, TargetVoltage,
+             MilliAmps, MilliVolts, BATTERY_LEVEL_MISSING, RAILS};
+use axp209::boards::BOARDS;
+use axp209::rail::GPIO_LDO_VOLTAGE;
+use axp209::register_dump::REGISTERS;
+use linux_hal::I2cdev;
+use linux_hal::i2cdev::linux::LinuxI2CError;
+
+const USAGE: &str = "\
+Usage: axp209ctl [--bus <device>] [--board <name>] [--force] [--json] <command>
+
+Options:
+    --bus <device>    I2C bus the AXP209 is on (default /dev/i2c-0)
+    --board <name>    Keep to the rail limits of a board (chip, cubieboard, c64)
+    --force           Change rails and restore registers even if the board's
+                      limits say no
+    --json            Print JSON rather than text
+
+Commands:
+    status                          Power, charging and battery state
+    adc                             Every ADC channel that's switched on
+    rail                            Every rail, whether it's on and its voltage
+    rail enable <rail>              Switch a rail on (dcdc2, dcdc3, ldo2, ldo3, ldo4, exten)
+    rail disable <rail>             Switch a rail off
+    rail set-voltage <rail> <mV>    Set a rail's voltage
+    charge config [options]         Show the charger settings, or change them with
+                                      --enable, --disable, --current <mA>, --target <mV>
+    timer [<minutes>]               Show the wakeup timer, or (re)start it. 0 stops it
+    irq [clear]                     Show enabled and pending IRQs, or clear the pending ones
+    dump [<file>]                   Show every register, and save them to a file if given
+    restore <file>                  Put back registers saved with dump
+    watch [<ms>]                    Show the status over and over, every second by default";
+
+type Pmic = Axp209<I2cdev>;
+
+struct Options {
+    bus: String,
+    board: Option<&'static Board>,
+    force: bool,
+    json: bool,
+    command: Vec<String>,
+}
+
+fn main() {
+    let options = match parse_options(env::args().skip(1)) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("axp209ctl: {}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("axp209ctl: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_options<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut options = Options {
+        bus: "/dev/i2c-0".to_string(),
+        board: None,
+        force: false,
+        json: false,
+        command: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bus" => options.bus = args.next().ok_or("--bus needs a device")?,
+            "--board" => options.board = Some(parse_board(&args.next().ok_or("--board needs a name")?)?),
+            "--force" => options.force = true,
+            "--json" => options.json = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            },
+            _ => options.command.push(arg),
+        }
+    }
+
+    if options.command.is_empty() {
+        return Err("No command given".to_string());
+    }
+
+    Ok(options)
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let args: Vec<&str> = options.command.iter().map(|x| x.as_str()).collect();
+    let json = options.json;
+
+    let i2c = I2cdev::new(&options.bus).map_err(|e| format!("Can't open {}: {}", options.bus, e))?;
+    let mut pmic = match options.board {
+        Some(board) => Axp209::with_rail_policy(i2c, board.rail_policy()),
+        None => Axp209::new(i2c),
+    };
+    let force = options.force;
+
+    match args.as_slice() {
+        ["status"] => status(&mut pmic, json),
+        ["adc"] => adc(&mut pmic, json),
+        ["rail"] => rails(&mut pmic, json),
+        ["rail", "enable", rail] => {
+            let rail = parse_rail(rail)?;
+            forced(&mut pmic, force, |pmic| pmic.set_rail_enabled(rail, true)).map_err(describe)
+        },
+        ["rail", "disable", rail] => {
+            let rail = parse_rail(rail)?;
+            forced(&mut pmic, force, |pmic| pmic.set_rail_enabled(rail, false)).map_err(describe)
+        },
+        ["rail", "set-voltage", rail, voltage] => set_voltage(&mut pmic, rail, voltage, force),
+        ["charge", "config", rest @ ..] => charge_config(&mut pmic, rest, json),
+        ["timer"] => timer(&mut pmic, json),
+        ["timer", minutes] => set_timer(&mut pmic, minutes),
+        ["irq"] => irq(&mut pmic, json),
+        ["irq", "clear"] => clear_irq(&mut pmic),
+        ["dump"] => dump(&mut pmic, None, json),
+        ["dump", file] => dump(&mut pmic, Some(file), json),
+        ["restore", file] => restore(&mut pmic, file, force),
+        ["watch"] => watch(&mut pmic, 1000, json),
+        ["watch", ms] => watch(&mut pmic, parse_number(ms)?, json),
+        _ => Err(format!("Unknown command: {}\n\n{}", args.join(" "), USAGE)),
+    }
+}
+
+/// Run `f` with the rail policy out of the way if `force` is set
+fn forced<T, F>(pmic: &mut Pmic, force: bool, f: F) -> T
+where
+    F: FnOnce(&mut Pmic) -> T,
+{
+    if force {
+        pmic.without_rail_policy(f)
+    } else {
+        f(pmic)
+    }
+}
+
+/// Make a driver error fit to print
+fn describe(error: Error<LinuxI2CError>) -> String {
+    match error {
+        Error::I2c(e) => format!("I2C error: {}", e),
+        Error::RailPolicy(e) => format!("{} (--force to do it anyway)", e),
+        Error::VoltageOutOfRange(rail, _) => match rail.voltage_field() {
+            Some(field) => format!("{} can only be set between {} and {}", rail, field.min, field.max),
+            None => format!("{} doesn't have a voltage", rail),
+        },
+        Error::NoVoltage(rail) => format!("{} doesn't have a voltage", rail),
+        Error::GpioVoltageOutOfRange(_) => {
+            let field = GPIO_LDO_VOLTAGE;
+            format!("The GPIO LDO can only be set between {} and {}", field.min, field.max)
+        },
+        e => format!("{:?}", e),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Not a number: {}", value))
+}
+
+fn parse_rail(name: &str) -> Result<Rail, String> {
+    RAILS.iter()
+        .find(|x| x.to_string().eq_ignore_ascii_case(name))
+        .cloned()
+        .ok_or_else(|| format!("Unknown rail: {}", name))
+}
+
+/// A board by its name, or any word of it, ignoring case and punctuation,
+/// so "chip" finds the C.H.I.P.
+fn parse_board(name: &str) -> Result<&'static Board, String> {
+    let simplify = |x: &str| -> String {
+        x.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+    };
+    let wanted = simplify(name);
+
+    BOARDS.iter()
+        .find(|board| {
+            simplify(board.name) == wanted || board.name.split_whitespace().any(|x| simplify(x) == wanted)
+        })
+        .ok_or_else(|| format!("Unknown board: {}", name))
+}
+
+/// A string, quoted and escaped for JSON
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// The names of the flags set in a bitflags value, from its `Debug`
+fn flag_names<T: Debug>(value: T) -> Vec<String> {
+    let text = format!("{:?}", value);
+
+    if text == "(empty)" {
+        return Vec::new();
+    }
+
+    text.split(" | ").map(|x| x.to_string()).collect()
+}
+
+fn json_flags<T: Debug>(value: T) -> String {
+    let names: Vec<String> = flag_names(value).iter().map(|x| json_string(x)).collect();
+
+    format!("[{}]", names.join(","))
+}
+
+fn text_flags<T: Debug>(value: T) -> String {
+    let names = flag_names(value);
+
+    if names.is_empty() {
+        "-".to_string()
+    } else {
+        names.join(" | ")
+    }
+}
+
+fn status(pmic: &mut Pmic, json: bool) -> Result<(), String> {
+    let power = pmic.power_status().map_err(describe)?;
+    let charging = pmic.charging_status().map_err(describe)?;
+    let outputs = pmic.power_control().map_err(describe)?;
+    let state = pmic.battery_state().map_err(describe)?;
+    let level = pmic.battery_level().map_err(describe)?;
+    let voltage = pmic.battery_voltage().map_err(describe)?;
+    let temperature = pmic.temperature().map_err(describe)?;
+    let level = if state == BatteryState::Absent || level == BATTERY_LEVEL_MISSING {
+        None
+    } else {
+        Some(level)
+    };
+
+    if json {
+        println!("{{\"power_status\":{},\"charging_status\":{},\"outputs\":{},\"battery_state\":{},\
+                  \"battery_level\":{},\"battery_voltage_mv\":{},\"temperature_c\":{:.1}}}",
+                 json_flags(power), json_flags(charging), json_flags(outputs),
+                 json_string(&format!("{:?}", state)),
+                 level.map_or("null".to_string(), |x| x.to_string()),
+                 voltage.0, temperature.0 as f32 / 10.0);
+    } else {
+        println!("Power:        {}", text_flags(power));
+        println!("Charging:     {}", text_flags(charging));
+        println!("Outputs:      {}", text_flags(outputs));
+        match level {
+            Some(x) => println!("Battery:      {:?}, {}%, {}", state, x, voltage),
+            None => println!("Battery:      {:?}", state),
+        }
+        println!("Temperature:  {}", temperature);
+    }
+
+    Ok(())
+}
+
+fn adc(pmic: &mut Pmic, json: bool) -> Result<(), String> {
+    // Strict mode tells us which channels are switched off, rather than
+    // printing whatever stale value they hold
+    pmic.set_strict(true);
+    let mut fields = Vec::new();
+
+    for channel in Channel::ALL.iter() {
+        let reading = match pmic.read_channel(*channel) {
+            Ok(x) => Some(x),
+            Err(Error::ChannelDisabled(_)) => None,
+            Err(e) => return Err(describe(e)),
+        };
+
+        if json {
+            let value = match reading {
+                Some(Reading::Voltage(x)) => format!("{{\"value\":{},\"unit\":\"uV\"}}", x.0),
+                Some(Reading::Current(x)) => format!("{{\"value\":{},\"unit\":\"uA\"}}", x.0),
+                Some(Reading::Temperature(x)) => format!("{{\"value\":{:.1},\"unit\":\"C\"}}", x.0 as f32 / 10.0),
+                Some(Reading::Power(x)) => format!("{{\"value\":{},\"unit\":\"uW\"}}", x.0),
+                None => "null".to_string(),
+            };
+            fields.push(format!("{}:{}", json_string(&format!("{:?}", channel)), value));
+        } else {
+            match reading {
+                Some(x) => println!("{:<24} {}", format!("{:?}", channel), x),
+                None => println!("{:<24} off", format!("{:?}", channel)),
+            }
+        }
+    }
+
+    if json {
+        println!("{{{}}}", fields.join(","));
+    }
+
+    Ok(())
+}
+
+fn rails(pmic: &mut Pmic, json: bool) -> Result<(), String> {
+    let outputs = pmic.power_control().map_err(describe)?;
+    let mut fields = Vec::new();
+
+    for rail in RAILS.iter() {
+        let on = outputs.contains(rail.flag());
+        let voltage = match rail.voltage_field() {
+            Some(_) => Some(pmic.rail_voltage(*rail).map_err(describe)?),
+            None => None,
+        };
+
+        if json {
+            fields.push(format!("{}:{{\"enabled\":{},\"voltage_mv\":{}}}",
+                                json_string(&rail.to_string().to_lowercase()), on,
+                                voltage.map_or("null".to_string(), |x| x.0.to_string())));
+        } else {
+            let voltage = voltage.map_or(String::new(), |x| x.to_string());
+            println!("{:<6} {:<4} {}", rail.to_string(), if on { "on" } else { "off" }, voltage);
+        }
+    }
+
+    if json {
+        println!("{{{}}}", fields.join(","));
+    }
+
+    Ok(())
+}
+
+fn set_voltage(pmic: &mut Pmic, rail: &str, voltage: &str, force: bool) -> Result<(), String> {
+    let rail = parse_rail(rail)?;
+    let voltage = MilliVolts(parse_number(voltage)?);
+
+    forced(pmic, force, |pmic| pmic.set_rail_voltage(rail, voltage)).map_err(describe)
+}
+
+fn charge_config(pmic: &mut Pmic, args: &[&str], json: bool) -> Result<(), String> {
+    let mut enable = None;
+    let mut current = None;
+    let mut target = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--enable" => enable = Some(true),
+            "--disable" => enable = Some(false),
+            // Out of range values get clamped, and what was actually set is
+            // printed afterwards
+            "--current" => current = Some(MilliAmps(parse_number(args.next().ok_or("--current needs a value")?)?)),
+            "--target" => {
+                let value = MilliVolts(parse_number(args.next().ok_or("--target needs a value")?)?);
+                let found = TargetVoltage::ALL.iter().find(|x| x.millivolts() == value)
+                    .ok_or("Target voltage can only be 4100, 4150, 4200 or 4360mV")?;
+                target = Some(*found);
+            },
+            x => return Err(format!("Unknown charge option: {}", x)),
+        }
+    }
+
+    if enable.is_some() || current.is_some() || target.is_some() {
+        pmic.modify_charge_control(|charge| {
+            if let Some(x) = enable {
+                charge.set(axp209::ChargeControl::ENABLE, x);
+            }
+            if let Some(x) = current {
+                charge.set_current(x);
+            }
+            if let Some(x) = target {
+                charge.set_target_voltage(x);
+            }
+        }).map_err(describe)?;
+    }
+
+    let charge = pmic.charge_control().map_err(describe)?;
+    let enabled = charge.contains(axp209::ChargeControl::ENABLE);
+
+    if json {
+        println!("{{\"enabled\":{},\"target_mv\":{},\"current_ma\":{}}}",
+                 enabled, charge.target_voltage().millivolts().0, charge.current().0);
+    } else {
+        println!("Charging:  {}", if enabled { "enabled" } else { "disabled" });
+        println!("Target:    {}", charge.target_voltage().millivolts());
+        println!("Current:   {}", charge.current());
+    }
+
+    Ok(())
+}
+
+fn timer(pmic: &mut Pmic, json: bool) -> Result<(), String> {
+    let timer = pmic.timer_control().map_err(describe)?;
+
+    if json {
+        println!("{{\"minutes\":{},\"expired\":{}}}", timer.minutes(), timer.expired());
+    } else {
+        println!("Minutes:  {}", timer.minutes());
+        println!("Expired:  {}", timer.expired());
+    }
+
+    Ok(())
+}
+
+fn set_timer(pmic: &mut Pmic, minutes: &str) -> Result<(), String> {
+    let minutes: u8 = parse_number(minutes)?;
+
+    if minutes >= 127 {
+        return Err("The timer can only be set up to 126 minutes".to_string());
+    }
+
+    pmic.modify_timer_control(|timer| {
+        timer.set_minutes(minutes);
+        // Writing the expired bit restarts the countdown
+        timer.set_expired(true);
+    }).map_err(describe)
+}
+
+fn irq(pmic: &mut Pmic, json: bool) -> Result<(), String> {
+    let enabled = pmic.irq_enable().map_err(describe)?;
+    let pending = pmic.irq_status().map_err(describe)?;
+
+    if json {
+        println!("{{\"enabled\":{},\"pending\":{}}}", json_flags(enabled), json_flags(pending));
+    } else {
+        println!("Enabled:  {}", text_flags(enabled));
+        println!("Pending:  {}", text_flags(pending));
+    }
+
+    Ok(())
+}
+
+fn clear_irq(pmic: &mut Pmic) -> Result<(), String> {
+    let pending = pmic.irq_status().map_err(describe)?;
+
+    pmic.clear_irq(pending & Irq::all()).map_err(describe)
+}
+
+fn dump(pmic: &mut Pmic, file: Option<&str>, json: bool) -> Result<(), String> {
+    let dump = pmic.dump_registers().map_err(describe)?;
+
+    if let Some(file) = file {
+        fs::write(file, &dump.as_bytes()[..]).map_err(|e| format!("Can't write {}: {}", file, e))?;
+    }
+
+    if json {
+        let fields: Vec<String> = REGISTERS.iter()
+            .map(|x| format!("\"0x{:02x}\":{}", x.address, dump.get(x.address)))
+            .collect();
+        println!("{{{}}}", fields.join(","));
+    } else {
+        print!("{}", dump);
+    }
+
+    Ok(())
+}
+
+fn restore(pmic: &mut Pmic, file: &str, force: bool) -> Result<(), String> {
+    let bytes = fs::read(file).map_err(|e| format!("Can't read {}: {}", file, e))?;
+
+    if bytes.len() != 256 {
+        return Err(format!("{} isn't a register dump, it should be 256 bytes", file));
+    }
+
+    let mut values = [0u8; 256];
+    values.copy_from_slice(&bytes);
+
+    let dump = RegisterDump::new(values);
+
+    forced(pmic, force, |pmic| pmic.restore_registers(&dump)).map_err(describe)
+}
+
+fn watch(pmic: &mut Pmic, interval: u64, json: bool) -> Result<(), String> {
+    loop {
+        status(pmic, json)?;
+
+        if !json {
+            println!();
+        }
+
+        thread::sleep(Duration::from_millis(interval));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axp209::{ChargingStatus, PowerControl};
+
+    fn args(line: &str) -> impl Iterator<Item = String> + '_ {
+        line.split_whitespace().map(|x| x.to_string())
+    }
+
+    #[test]
+    fn options() {
+        let options = parse_options(args("rail set-voltage ldo3 3300")).unwrap();
+        assert_eq!(options.bus, "/dev/i2c-0");
+        assert!(options.board.is_none());
+        assert!(!options.force && !options.json);
+        assert_eq!(options.command, ["rail", "set-voltage", "ldo3", "3300"]);
+
+        let options = parse_options(args("--bus /dev/i2c-1 --board chip dump --json --force")).unwrap();
+        assert_eq!(options.bus, "/dev/i2c-1");
+        assert_eq!(options.board.unwrap().name, "NTC C.H.I.P.");
+        assert!(options.force && options.json);
+        assert_eq!(options.command, ["dump"]);
+
+        assert!(parse_options(args("")).is_err());
+        assert!(parse_options(args("--json")).is_err());
+        assert!(parse_options(args("status --bus")).is_err());
+        assert!(parse_options(args("--board pinephone status")).is_err());
+    }
+
+    #[test]
+    fn board_names() {
+        assert_eq!(parse_board("NTC C.H.I.P.").unwrap().name, "NTC C.H.I.P.");
+        assert_eq!(parse_board("CubieBoard").unwrap().name, "Cubieboard");
+        assert!(parse_board("ntc").is_ok());
+        assert_eq!(parse_board("c64").unwrap().name, "C64 Mini");
+        assert!(parse_board("").is_err());
+    }
+
+    #[test]
+    fn rail_names() {
+        assert_eq!(parse_rail("ldo3"), Ok(Rail::Ldo3));
+        assert_eq!(parse_rail("DCDC2"), Ok(Rail::Dcdc2));
+        assert_eq!(parse_rail("Exten"), Ok(Rail::Exten));
+        assert!(parse_rail("ldo1").is_err());
+        assert!(parse_rail("").is_err());
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(json_string(""), "\"\"");
+        assert_eq!(json_string("Charging"), "\"Charging\"");
+        assert_eq!(json_string("a \"b\" \\ c"), "\"a \\\"b\\\" \\\\ c\"");
+        assert_eq!(json_string("\n\t\u{1}"), "\"\\u000a\\u0009\\u0001\"");
+        assert_eq!(json_string("47.3°C"), "\"47.3°C\"");
+    }
+
+    // These lean on bitflags' Debug output, so they'll catch it changing
+    #[test]
+    fn flags() {
+        assert_eq!(flag_names(PowerControl::DCDC2 | PowerControl::LDO3), ["LDO3", "DCDC2"]);
+        assert_eq!(flag_names(PowerControl::empty()), Vec::<String>::new());
+        assert_eq!(json_flags(ChargingStatus::BATTERY_PRESENT), "[\"BATTERY_PRESENT\"]");
+        assert_eq!(json_flags(ChargingStatus::empty()), "[]");
+        assert_eq!(text_flags(PowerControl::empty()), "-");
+    }
+}
@@ -0,0 +1,228 @@
+//! What the outputs are wired to on boards this crate has been used with,
+//! so application code can ask for "the WiFi rail" rather than remember
+//! which LDO that is on which board:
+//!
+//! ```ignore
+//! let board = &boards::CHIP;
+//! let mut pmic = Axp209::with_rail_policy(i2c, board.rail_policy());
+//!
+//! board.rail(Function::Wifi).disable(&mut pmic)?;
+//! ```
+//!
+//! The voltage ranges are the ones the mainline Linux device trees allow,
+//! and `rail_policy()` turns them into a `RailPolicy` that also stops the
+//! CPU and core rails being switched off. If your board isn't here, make
+//! your own `Board`, it's just a table.
+//!
+//! GPIO0 can be run as an LDO too (`Output::GpioLdo`). None of the boards
+//! here use it, but your own can. The rail policy only covers the outputs
+//! in `PowerControl`, so it doesn't guard the GPIO LDO.
+
+use hal::blocking::i2c::{Read, Write, WriteRead};
+
+use error::Error;
+use power_control::PowerControl;
+use rail::Rail;
+use rail_policy::RailPolicy;
+use units::MilliVolts;
+use Axp209;
+
+/// Something on the board that gets its power from the AXP209
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Function {
+    /// The CPU cores
+    Cpu,
+    /// The rest of the SoC (VDD-INT/DLL), which is just as fatal to lose
+    Core,
+    /// The real time clock
+    Rtc,
+    /// Analog supply for the SoC (AVCC)
+    Analog,
+    /// The WiFi/Bluetooth module
+    Wifi,
+    /// The 5V supply to USB and the headers
+    Usb5v,
+}
+
+/// An output on the chip. LDO1 and the GPIO LDO aren't switched through
+/// `PowerControl`, so they aren't a `Rail`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Output {
+    Rail(Rail),
+    /// Always on, and fixed by the hardware
+    Ldo1,
+    /// GPIO0 set up as a low noise LDO, 1.8V to 3.3V (LDO5 to Linux). See
+    /// `Axp209::set_gpio_ldo_enabled()`.
+    GpioLdo,
+}
+
+/// One output and what it's wired to
+#[derive(Debug, Clone, Copy)]
+pub struct Connection {
+    pub output: Output,
+    pub function: Function,
+    /// The voltages that are safe to set, inclusive
+    pub voltage: (MilliVolts, MilliVolts),
+    /// Whether switching it off takes the board down with it
+    pub critical: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Board {
+    pub name: &'static str,
+    pub connections: &'static [Connection],
+}
+
+macro_rules! connection {
+    ($output:expr, $function:ident, $min:expr, $max:expr, $critical:expr) => {
+        Connection {
+            output: $output,
+            function: Function::$function,
+            voltage: (MilliVolts($min), MilliVolts($max)),
+            critical: $critical,
+        }
+    };
+}
+
+/// Next Thing Co.'s C.H.I.P. Both LDO3 and LDO4 feed the WiFi module, and
+/// EXTEN enables the LP6226 boost converter for the 5V rail.
+pub const CHIP: Board = Board {
+    name: "NTC C.H.I.P.",
+    connections: &[
+        connection!(Output::Rail(Rail::Dcdc2), Cpu, 1000, 1400, true),
+        connection!(Output::Rail(Rail::Dcdc3), Core, 1000, 1250, true),
+        connection!(Output::Ldo1, Rtc, 1300, 1300, true),
+        connection!(Output::Rail(Rail::Ldo2), Analog, 2700, 3300, false),
+        connection!(Output::Rail(Rail::Ldo3), Wifi, 3300, 3300, false),
+        connection!(Output::Rail(Rail::Ldo4), Wifi, 3300, 3300, false),
+        connection!(Output::Rail(Rail::Exten), Usb5v, 5000, 5000, false),
+    ],
+};
+
+/// Cubietech's A10/A20 Cubieboards and Cubietruck. LDO3 and LDO4 aren't
+/// used.
+pub const CUBIEBOARD: Board = Board {
+    name: "Cubieboard",
+    connections: &[
+        connection!(Output::Rail(Rail::Dcdc2), Cpu, 1000, 1450, true),
+        connection!(Output::Rail(Rail::Dcdc3), Core, 1000, 1400, true),
+        connection!(Output::Ldo1, Rtc, 1300, 1300, true),
+        connection!(Output::Rail(Rail::Ldo2), Analog, 3000, 3000, false),
+    ],
+};
+
+/// Retro Games Ltd's C64 Mini. This follows the Allwinner reference design
+/// the board is based on, and hasn't been traced on real hardware, so
+/// double check before relying on it.
+pub const C64_MINI: Board = Board {
+    name: "C64 Mini",
+    connections: &[
+        connection!(Output::Rail(Rail::Dcdc2), Cpu, 1000, 1400, true),
+        connection!(Output::Rail(Rail::Dcdc3), Core, 1000, 1400, true),
+        connection!(Output::Ldo1, Rtc, 1300, 1300, true),
+        connection!(Output::Rail(Rail::Ldo2), Analog, 3000, 3000, false),
+    ],
+};
+
+/// Every board profile this crate knows about
+pub const BOARDS: &[Board] = &[CHIP, CUBIEBOARD, C64_MINI];
+
+impl Board {
+    /// What an output is wired to, if anything
+    pub fn connection(&self, output: Output) -> Option<&Connection> {
+        self.connections.iter().find(|x| x.output == output)
+    }
+
+    /// The rails powering something. Some things need more than one.
+    pub fn rails(&self, function: Function) -> impl Iterator<Item = Rail> + '_ {
+        self.connections.iter()
+            .filter(move |x| x.function == function)
+            .filter_map(|x| match x.output {
+                Output::Rail(rail) => Some(rail),
+                _ => None,
+            })
+    }
+
+    /// The `PowerControl` bits for everything powering something
+    pub fn flags(&self, function: Function) -> PowerControl {
+        self.rails(function).fold(PowerControl::empty(), |acc, x| acc | x.flag())
+    }
+
+    /// Whether something gets power from the GPIO LDO
+    pub fn uses_gpio_ldo(&self, function: Function) -> bool {
+        self.connections.iter().any(|x| x.function == function && x.output == Output::GpioLdo)
+    }
+
+    /// A handle for switching something on and off, whichever outputs it
+    /// takes to do it
+    pub fn rail(&self, function: Function) -> BoardRail<'_> {
+        BoardRail {
+            board: self,
+            function,
+        }
+    }
+
+    /// A policy that keeps the critical rails on and every rail within its
+    /// safe voltages. Rails without a voltage of their own (EXTEN) only get
+    /// the critical check.
+    pub fn rail_policy(&self) -> RailPolicy {
+        let mut policy = RailPolicy::new();
+
+        for connection in self.connections {
+            let rail = match connection.output {
+                Output::Rail(x) => x,
+                _ => continue,
+            };
+
+            if connection.critical {
+                policy = policy.critical(rail);
+            }
+
+            if rail.voltage_field().is_some() {
+                policy = policy.window(rail, connection.voltage.0, connection.voltage.1);
+            }
+        }
+
+        policy
+    }
+}
+
+/// Everything powering one thing on a board. Get one with `Board::rail()`.
+/// LDO1 is always on, so it's left out of all of this.
+#[derive(Debug, Clone, Copy)]
+pub struct BoardRail<'a> {
+    board: &'a Board,
+    function: Function,
+}
+
+impl<'a> BoardRail<'a> {
+    pub fn function(&self) -> Function {
+        self.function
+    }
+
+    /// Whether every output powering it is on
+    pub fn is_enabled<I2C, E>(&self, pmic: &mut Axp209<I2C>) -> Result<bool, Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+    {
+        if self.board.uses_gpio_ldo(self.function) && !pmic.gpio_ldo_enabled()? {
+            return Ok(false);
+        }
+
+        Ok(pmic.power_control()?.contains(self.board.flags(self.function)))
+    }
+
+    pub fn enable<I2C, E>(&self, pmic: &mut Axp209<I2C>) -> Result<(), Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+    {
+        pmic.set_function_enabled(self.board, self.function, true)
+    }
+
+    pub fn disable<I2C, E>(&self, pmic: &mut Axp209<I2C>) -> Result<(), Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+    {
+        pmic.set_function_enabled(self.board, self.function, false)
+    }
+}
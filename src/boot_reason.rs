@@ -0,0 +1,64 @@
+//! Works out why the system came up by looking at which power-on events
+//! the chip has latched in its IRQ status registers. Plug-in events only
+//! count if `PowerStatus::START_ON_POWER` agrees that the chip was started
+//! by an input source, as otherwise the power was plugged in later on.
+//!
+//! The IRQ status bits survive a reboot of the SoC, so if nothing clears
+//! them you'd see the same reason forever. `Axp209::boot_reason()` clears
+//! the events it looked at, which means the first caller after boot gets the
+//! answer and everyone after that gets `WarmReboot`. If the kernel or some
+//! other program is also handling the chip's interrupts, it may have
+//! cleared them before you got a look.
+//!
+//! The same goes for power key presses while the system was running. If
+//! nothing cleared those before a warm reboot, there's no telling them
+//! apart from the press that turned the chip on, and you'll get `PowerKey`.
+
+use irq::Irq;
+use power_status::PowerStatus;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BootReason {
+    /// Someone pressed the power key
+    PowerKey,
+    /// Power was plugged into VBUS (usually USB)
+    VbusInserted,
+    /// Power was plugged into ACIN
+    AcinInserted,
+    /// The countdown timer expired (see `timer_control`)
+    TimerExpired,
+    /// The chip stayed powered the whole time and only the rest of the
+    /// system restarted. A brown-out of the SoC, a watchdog or plain old
+    /// `reboot` all end up here.
+    WarmReboot,
+}
+
+/// All the events `BootReason` is decided from
+pub const BOOT_EVENTS: Irq = Irq::from_bits_truncate(
+    Irq::PEK_SHORT_PRESS.bits() | Irq::PEK_LONG_PRESS.bits() | Irq::PEK_PRESSED.bits()
+    | Irq::PEK_RELEASED.bits() | Irq::VBUS_PLUGGED.bits() | Irq::ACIN_PLUGGED.bits()
+    | Irq::TIMER.bits()
+);
+
+impl BootReason {
+    /// Decide the boot reason from the raw IRQ status and power status. The
+    /// chip knows for sure when an input started it, so that's checked
+    /// first, and any power key events alongside it happened later on. No
+    /// power-on events at all means the chip never turned off.
+    pub fn new(irq: Irq, power: PowerStatus) -> Self {
+        if power.contains(PowerStatus::START_ON_POWER)
+           && irq.contains(Irq::ACIN_PLUGGED) {
+            BootReason::AcinInserted
+        } else if power.contains(PowerStatus::START_ON_POWER)
+                  && irq.contains(Irq::VBUS_PLUGGED) {
+            BootReason::VbusInserted
+        } else if irq.intersects(Irq::PEK_SHORT_PRESS | Irq::PEK_LONG_PRESS
+                                 | Irq::PEK_PRESSED | Irq::PEK_RELEASED) {
+            BootReason::PowerKey
+        } else if irq.contains(Irq::TIMER) {
+            BootReason::TimerExpired
+        } else {
+            BootReason::WarmReboot
+        }
+    }
+}
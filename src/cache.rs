@@ -0,0 +1,244 @@
+//! An optional layer around `Axp209` that remembers the configuration
+//! registers so they don't have to be re-read over the bus every time.
+//! Changes are made to the cached copy and only sent to the chip when
+//! `flush()` is called, and then only for registers that actually changed.
+//!
+//! Only plain read/write configuration registers are cached. Status, ADC,
+//! IRQ status and anything with a bit that does something when written
+//! (the timer, shutdown and coulomb counter control) always go to the
+//! chip, through `pmic()`. Keeping a copy of those would mean writing the
+//! bit again on the next flush, and shutting the chip down twice isn't a
+//! good look.
+//!
+//! If something else on the system (the kernel, for instance) changes the
+//! chip behind your back, call `invalidate()`.
+//!
+//! ```ignore
+//!     let i2c = I2cdev::new("/dev/i2c-0").unwrap();
+//!     let mut pmic = CachedAxp209::new(Axp209::new(i2c));
+//!
+//!     // Only the first of these touches the bus
+//!     let _ = pmic.adc_control().unwrap();
+//!     let mut adc = pmic.adc_control().unwrap();
+//!
+//!     adc.set_gpio0(true);
+//!     pmic.set_adc_control(adc);
+//!     pmic.flush().unwrap();
+//!
+//!     // ADC values are never cached
+//!     let voltage = pmic.pmic().battery_voltage().unwrap();
+//! ```
+
+use hal::blocking::i2c::{Read, Write, WriteRead};
+
+use adc_control::AdcControl;
+use irq::{Irq, IRQ_BANKS};
+use over_temperature::OverTemperatureControl;
+use power_control::PowerControl;
+use register_dump::{restore_mask, Access, REGISTERS};
+use {Axp209, Error, Registers};
+
+/// One bit for each of the 256 register addresses
+#[derive(Clone, Copy)]
+struct RegisterSet([u32; 8]);
+
+impl RegisterSet {
+    fn empty() -> Self {
+        RegisterSet([0; 8])
+    }
+
+    fn contains(&self, address: u8) -> bool {
+        self.0[address as usize / 32] & (1 << (address % 32)) != 0
+    }
+
+    fn insert(&mut self, address: u8) {
+        self.0[address as usize / 32] |= 1 << (address % 32);
+    }
+
+    fn remove(&mut self, address: u8) {
+        self.0[address as usize / 32] &= !(1 << (address % 32));
+    }
+}
+
+/// Whether a register is safe to keep a copy of
+pub fn is_cacheable(address: u8) -> bool {
+    restore_mask(address) == 0
+        && REGISTERS.iter().any(|x| x.address == address && x.access == Access::ReadWrite)
+}
+
+pub struct CachedAxp209<I2C> {
+    pmic: Axp209<I2C>,
+    values: [u8; 256],
+    valid: RegisterSet,
+    dirty: RegisterSet,
+}
+
+impl<I2C, E> CachedAxp209<I2C>
+where
+    I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+{
+    pub fn new(pmic: Axp209<I2C>) -> Self {
+        CachedAxp209 {
+            pmic,
+            values: [0; 256],
+            valid: RegisterSet::empty(),
+            dirty: RegisterSet::empty(),
+        }
+    }
+
+    /// Get the driver back. Anything not flushed is lost.
+    pub fn into_inner(self) -> Axp209<I2C> {
+        self.pmic
+    }
+
+    /// Uncached access to the chip, for reading ADC values and status
+    /// registers. Don't use it to change registers that are cached here.
+    pub fn pmic(&mut self) -> &mut Axp209<I2C> {
+        &mut self.pmic
+    }
+
+    /// Read a register, from the cache if it's there. Registers that can't
+    /// be cached are always read from the chip.
+    pub fn read_register(&mut self, address: u8) -> Result<u8, Error<E>> {
+        if self.valid.contains(address) {
+            return Ok(self.values[address as usize]);
+        }
+
+        let value = self.pmic.get_8bit_register(address)?;
+
+        if is_cacheable(address) {
+            self.values[address as usize] = value;
+            self.valid.insert(address);
+        }
+
+        Ok(value)
+    }
+
+    /// Change the cached copy of a register. It'll be written on `flush()`.
+    ///
+    /// Registers that can't be cached give `Error::NotCacheable`, as writing
+    /// those has side effects that shouldn't be put off.
+    pub fn write_register(&mut self, address: u8, value: u8) -> Result<(), Error<E>> {
+        if !is_cacheable(address) {
+            return Err(Error::NotCacheable(address));
+        }
+
+        self.store(address, value);
+
+        Ok(())
+    }
+
+    /// Change the cached copy of a register that's known to be cacheable
+    fn store(&mut self, address: u8, value: u8) {
+        if self.valid.contains(address) && self.values[address as usize] == value {
+            return;
+        }
+
+        self.values[address as usize] = value;
+        self.valid.insert(address);
+        self.dirty.insert(address);
+    }
+
+    /// Read-modify-write a register on the cached copy
+    pub fn modify_register<F>(&mut self, address: u8, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(u8) -> u8,
+    {
+        let value = self.read_register(address)?;
+
+        self.write_register(address, f(value))
+    }
+
+    /// Whether there are changes that haven't been written to the chip yet
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.0.iter().any(|x| *x != 0)
+    }
+
+    /// Write every changed register to the chip. Power control goes last so
+    /// voltages are in place before outputs get switched on.
+    pub fn flush(&mut self) -> Result<(), Error<E>> {
+        let power_control = Registers::PowerControl as u8;
+
+        // Nothing gets written if any of it breaks the rail policy
+        for register in REGISTERS {
+            if self.dirty.contains(register.address) {
+                self.pmic.check_rail_policy(register.address, self.values[register.address as usize])?;
+            }
+        }
+
+        for register in REGISTERS {
+            if register.address != power_control {
+                self.flush_register(register.address)?;
+            }
+        }
+
+        self.flush_register(power_control)
+    }
+
+    fn flush_register(&mut self, address: u8) -> Result<(), Error<E>> {
+        if self.dirty.contains(address) {
+            let value = self.values[address as usize];
+
+            // Power control gets checked, same as when it's set directly
+            if address == Registers::PowerControl as u8 {
+                self.pmic.set_power_control(PowerControl::new(value))?;
+            } else {
+                self.pmic.set_8bit_register(address, value)?;
+            }
+
+            self.dirty.remove(address);
+        }
+
+        Ok(())
+    }
+
+    /// Forget everything cached, including changes that weren't flushed.
+    pub fn invalidate(&mut self) {
+        self.valid = RegisterSet::empty();
+        self.dirty = RegisterSet::empty();
+    }
+
+    pub fn power_control(&mut self) -> Result<PowerControl, Error<E>> {
+        Ok(PowerControl::new(self.read_register(Registers::PowerControl as u8)?))
+    }
+
+    pub fn set_power_control(&mut self, value: PowerControl) {
+        self.store(Registers::PowerControl as u8, value.bits());
+    }
+
+    pub fn adc_control(&mut self) -> Result<AdcControl, Error<E>> {
+        let high = self.read_register(Registers::AdcControl as u8)?;
+        let low = self.read_register(Registers::AdcControl as u8 + 1)?;
+
+        Ok(AdcControl::new((high as u16) << 8 | low as u16))
+    }
+
+    pub fn set_adc_control(&mut self, value: AdcControl) {
+        self.store(Registers::AdcControl as u8, (value.bits() >> 8) as u8);
+        self.store(Registers::AdcControl as u8 + 1, value.bits() as u8);
+    }
+
+    pub fn over_temperature_control(&mut self) -> Result<OverTemperatureControl, Error<E>> {
+        Ok(OverTemperatureControl::new(self.read_register(Registers::OverTemperature as u8)?))
+    }
+
+    pub fn set_over_temperature_control(&mut self, value: OverTemperatureControl) {
+        self.store(Registers::OverTemperature as u8, value.bits());
+    }
+
+    pub fn irq_enable(&mut self) -> Result<Irq, Error<E>> {
+        let mut banks = [0u8; IRQ_BANKS];
+
+        for (i, bank) in banks.iter_mut().enumerate() {
+            *bank = self.read_register(Registers::IrqEnable as u8 + i as u8)?;
+        }
+
+        Ok(Irq::from_banks(&banks))
+    }
+
+    pub fn set_irq_enable(&mut self, value: Irq) {
+        for (i, bank) in value.to_banks().iter().enumerate() {
+            self.store(Registers::IrqEnable as u8 + i as u8, *bank);
+        }
+    }
+}
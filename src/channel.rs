@@ -0,0 +1,204 @@
+//! Everything there is to know about each ADC channel lives in one table,
+//! `CHANNELS`: where the value is, how wide it is, what a step is worth and
+//! which `AdcControl` bit turns it on. All the channel getters on `Axp209`,
+//! `AdcSnapshot` and the functions in `convert` work from it, so adding a
+//! channel means adding a row here and nothing else.
+//!
+//! If you want to work with channels generically, `Axp209::read_channel()`
+//! hands back a `Reading` for any of them:
+//!
+//! ```ignore
+//!     for channel in &[Channel::AcinVoltage, Channel::VbusVoltage] {
+//!         println!("{:?}: {}", channel, pmic.read_channel(*channel).unwrap());
+//!     }
+//! ```
+
+use core::fmt;
+
+use adc_control::AdcControl;
+use convert;
+use units::{DeciCelsius, MicroAmps, MicroVolts, MicroWatts};
+
+/// One of the values the ADC can measure
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Channel {
+    AcinVoltage,
+    AcinCurrent,
+    VbusVoltage,
+    VbusCurrent,
+    Temperature,
+    TsVoltage,
+    Gpio0Voltage,
+    Gpio1Voltage,
+    BatteryPower,
+    BatteryVoltage,
+    BatteryChargeCurrent,
+    BatteryDischargeCurrent,
+    IpsoutVoltage,
+}
+
+/// What kind of thing a channel measures
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Quantity {
+    /// In microvolts
+    Voltage,
+    /// In microamps
+    Current,
+    /// In tenths of a degree celsius
+    Temperature,
+    /// In microwatts
+    Power,
+}
+
+/// How to read and convert one channel
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelInfo {
+    /// Where the value starts. Wider values carry on in the registers after.
+    pub register: u8,
+    /// How many bits the value has
+    pub bits: u8,
+    /// What one step is worth, in the base unit of the `Quantity`...
+    pub step: u32,
+    /// ...divided by this, for the odd step that isn't a whole number
+    pub step_divisor: u32,
+    /// What a raw value of zero means, in the base unit of the `Quantity`
+    pub offset: i32,
+    /// The bit(s) in `AdcControl` that need to be set for the value to be live
+    pub enable: AdcControl,
+    pub quantity: Quantity,
+}
+
+macro_rules! channel {
+    ($register:expr, $bits:expr, $step:expr, $offset:expr, $enable:expr, $quantity:ident) => {
+        channel!($register, $bits, $step, 1, $offset, $enable, $quantity)
+    };
+    ($register:expr, $bits:expr, $step:expr, $divisor:expr, $offset:expr, $enable:expr, $quantity:ident) => {
+        ChannelInfo {
+            register: $register,
+            bits: $bits,
+            step: $step,
+            step_divisor: $divisor,
+            offset: $offset,
+            enable: $enable,
+            quantity: Quantity::$quantity,
+        }
+    };
+}
+
+/// Every channel, in the same order as `Channel`
+pub const CHANNELS: [ChannelInfo; 13] = [
+    channel!(0x56, 12, convert::INPUT_VOLTAGE_STEP.0, 0, AdcControl::ACIN_VOLTAGE, Voltage),
+    channel!(0x58, 12, convert::ACIN_CURRENT_STEP.0, 0, AdcControl::ACIN_CURRENT, Current),
+    channel!(0x5a, 12, convert::INPUT_VOLTAGE_STEP.0, 0, AdcControl::VBUS_VOLTAGE, Voltage),
+    channel!(0x5c, 12, convert::VBUS_CURRENT_STEP.0, 0, AdcControl::VBUS_CURRENT, Current),
+    channel!(0x5e, 12, 1, convert::TEMPERATURE_OFFSET.0 as i32, AdcControl::TEMPERATURE, Temperature),
+    channel!(0x62, 12, convert::TS_VOLTAGE_STEP.0, 0, AdcControl::TS_FUNCTION, Voltage),
+    channel!(0x64, 12, convert::GPIO_VOLTAGE_STEP.0, 0, AdcControl::GPIO0, Voltage),
+    channel!(0x66, 12, convert::GPIO_VOLTAGE_STEP.0, 0, AdcControl::GPIO1, Voltage),
+    // Voltage step times current step, so 0.55uW
+    channel!(0x70, 24, 55, 100, 0,
+             AdcControl::from_bits_truncate(AdcControl::BATTERY_VOLTAGE.bits()
+                                            | AdcControl::BATTERY_CURRENT.bits()), Power),
+    channel!(0x78, 12, convert::BATTERY_VOLTAGE_STEP.0, 0, AdcControl::BATTERY_VOLTAGE, Voltage),
+    channel!(0x7a, 12, convert::BATTERY_CURRENT_STEP.0, 0, AdcControl::BATTERY_CURRENT, Current),
+    channel!(0x7c, 13, convert::BATTERY_CURRENT_STEP.0, 0, AdcControl::BATTERY_CURRENT, Current),
+    channel!(0x7e, 12, convert::IPSOUT_VOLTAGE_STEP.0, 0, AdcControl::APS_VOLTAGE, Voltage),
+];
+
+impl Channel {
+    /// Every channel, in the same order as `CHANNELS`
+    pub const ALL: [Channel; 13] = [
+        Channel::AcinVoltage,
+        Channel::AcinCurrent,
+        Channel::VbusVoltage,
+        Channel::VbusCurrent,
+        Channel::Temperature,
+        Channel::TsVoltage,
+        Channel::Gpio0Voltage,
+        Channel::Gpio1Voltage,
+        Channel::BatteryPower,
+        Channel::BatteryVoltage,
+        Channel::BatteryChargeCurrent,
+        Channel::BatteryDischargeCurrent,
+        Channel::IpsoutVoltage,
+    ];
+
+    /// The channel's row in `CHANNELS`
+    pub fn info(&self) -> &'static ChannelInfo {
+        &CHANNELS[*self as usize]
+    }
+}
+
+impl ChannelInfo {
+    /// How many registers the value is spread over
+    pub fn register_count(&self) -> usize {
+        (self.bits as usize).div_ceil(8)
+    }
+
+    /// Pull the raw value out of the channel's registers. `bytes` needs to
+    /// start at `register` and be at least `register_count()` long.
+    pub fn decode(&self, bytes: &[u8]) -> u32 {
+        // Every byte but the last is whole. The last one only holds however
+        // many bits are left over, right aligned. Weird way to store a
+        // number if ye ask me!
+        let whole = self.register_count() - 1;
+        let leftover = self.bits as usize - whole * 8;
+        let mut value: u32 = 0;
+
+        for byte in &bytes[..whole] {
+            value = value << 8 | *byte as u32;
+        }
+
+        value << leftover | (bytes[whole] as u32 & ((1 << leftover) - 1))
+    }
+
+    /// The opposite of `decode()`, for building register contents by hand.
+    /// Anything in `raw` above `bits` gets dropped.
+    pub fn encode(&self, raw: u32, bytes: &mut [u8]) {
+        let whole = self.register_count() - 1;
+        let leftover = self.bits as usize - whole * 8;
+
+        bytes[whole] = (raw & ((1 << leftover) - 1)) as u8;
+
+        for (i, byte) in bytes[..whole].iter_mut().enumerate() {
+            *byte = (raw >> (leftover + (whole - 1 - i) * 8)) as u8;
+        }
+    }
+
+    /// Convert a raw value into the base unit of the channel's `Quantity`
+    pub fn value(&self, raw: u32) -> i32 {
+        (raw as i64 * self.step as i64 / self.step_divisor as i64) as i32 + self.offset
+    }
+
+    /// Convert a raw value into a `Reading`
+    pub fn reading(&self, raw: u32) -> Reading {
+        let value = self.value(raw);
+
+        match self.quantity {
+            Quantity::Voltage => Reading::Voltage(MicroVolts(value as u32)),
+            Quantity::Current => Reading::Current(MicroAmps(value as u32)),
+            Quantity::Temperature => Reading::Temperature(DeciCelsius(value as i16)),
+            Quantity::Power => Reading::Power(MicroWatts(value as u32)),
+        }
+    }
+}
+
+/// A converted value from any channel
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Reading {
+    Voltage(MicroVolts),
+    Current(MicroAmps),
+    Temperature(DeciCelsius),
+    Power(MicroWatts),
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Reading::Voltage(x) => x.fmt(f),
+            Reading::Current(x) => x.fmt(f),
+            Reading::Temperature(x) => x.fmt(f),
+            Reading::Power(x) => x.fmt(f),
+        }
+    }
+}
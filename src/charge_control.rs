@@ -0,0 +1,90 @@
+//! Charge Control 1 (register 0x33) sets how the battery gets charged:
+//! whether it happens at all, what voltage to stop at, and how much current
+//! to push in.
+
+use units::{MilliAmps, MilliVolts};
+
+/// The voltage the charger takes the battery up to
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TargetVoltage {
+    Mv4100 = 0,
+    Mv4150 = 1,
+    Mv4200 = 2,
+    Mv4360 = 3,
+}
+
+impl TargetVoltage {
+    /// Every setting, lowest first
+    pub const ALL: [TargetVoltage; 4] = [
+        TargetVoltage::Mv4100,
+        TargetVoltage::Mv4150,
+        TargetVoltage::Mv4200,
+        TargetVoltage::Mv4360,
+    ];
+
+    pub fn millivolts(&self) -> MilliVolts {
+        match *self {
+            TargetVoltage::Mv4100 => MilliVolts(4100),
+            TargetVoltage::Mv4150 => MilliVolts(4150),
+            TargetVoltage::Mv4200 => MilliVolts(4200),
+            TargetVoltage::Mv4360 => MilliVolts(4360),
+        }
+    }
+}
+
+bitflags! {
+    /// Holds the state of the register. Changes will need to be committed
+    /// manually. The target voltage and current are multi-bit fields, so use
+    /// the methods for those.
+    pub struct ChargeControl: u8 {
+        /// Whether the battery gets charged at all
+        const ENABLE = 1 << 7;
+        /// Stop charging when the current drops to 15% of the charge current
+        /// rather than 10%
+        const END_CURRENT_15 = 1 << 4;
+    }
+}
+
+/// The lowest charge current that can be set
+const MIN_CURRENT: u16 = 300;
+/// The highest charge current that can be set
+const MAX_CURRENT: u16 = 1800;
+/// The step between charge current settings
+const CURRENT_STEP: u16 = 100;
+
+impl ChargeControl {
+    /// No checks are made here, and it's expected that it be populated by
+    /// the raw value from the axp209 chip.
+    pub fn new(value: u8) -> Self {
+        Self {
+            bits: value
+        }
+    }
+
+    pub fn target_voltage(&self) -> TargetVoltage {
+        match (self.bits >> 5) & 0b11 {
+            0 => TargetVoltage::Mv4100,
+            1 => TargetVoltage::Mv4150,
+            2 => TargetVoltage::Mv4200,
+            _ => TargetVoltage::Mv4360,
+        }
+    }
+
+    pub fn set_target_voltage(&mut self, value: TargetVoltage) {
+        self.bits = (self.bits & 0b1001_1111) | (value as u8) << 5;
+    }
+
+    /// How much current the charger pushes into the battery
+    pub fn current(&self) -> MilliAmps {
+        MilliAmps(MIN_CURRENT + (self.bits & 0x0f) as u16 * CURRENT_STEP)
+    }
+
+    /// Set the charge current. Value can be between 300mA and 1800mA in
+    /// steps of 100mA, anything in between is rounded down and anything
+    /// outside is clamped. `current()` tells you what you actually got.
+    pub fn set_current(&mut self, value: MilliAmps) {
+        let value = value.0.clamp(MIN_CURRENT, MAX_CURRENT);
+        let steps = ((value - MIN_CURRENT) / CURRENT_STEP) as u8;
+        self.bits = (self.bits & 0xf0) | steps;
+    }
+}
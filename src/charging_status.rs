@@ -1,21 +1,50 @@
 bitflags! {
     /// The Charging Status register reports on the state of the battery
-    /// and if the chip itself is overtemperature
+    /// and if the chip itself is overtemperature. Bits 4 and 0 are
+    /// reserved. All bits here are read-only.
     pub struct ChargingStatus: u8 {
         /// Whether the chip is at an unsafe temperature
         const OVERTEMPERATURE = 1 << 7;
-        /// Whether the battery is charging
+        /// Whether the battery is charging. This is false when charging
+        /// has finished as well as when it's not happening at all.
         const CHARGING = 1 << 6;
+        /// Whether there is a battery connected
         const BATTERY_PRESENT = 1 << 5;
-        const CELL_ACTIVATION_MODE = 1 << 3;    // "enetered cell activation mode"?!
+        /// Whether the battery has entered activation mode, which is
+        /// a slow pre-charge used to wake up a deeply discharged cell
+        const CELL_ACTIVATION_MODE = 1 << 3;
+        /// Whether the charge current is less than what was configured,
+        /// usually because the input can't supply enough power
         const CHARGE_CURRENT_LOW = 1 << 2;
+        /// Which mode the chip is running in (see `Mode`)
+        const MODE_B = 1 << 1;
     }
 }
 
+/// The two operating modes of the AXP209. Which one is in use is decided by
+/// the N_VBUSEN pin and can't be changed from software.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Mode {
+    /// VBUS is only used to charge the battery and supply the system
+    A,
+    /// VBUS is being driven by the board (USB OTG host) so the chip
+    /// won't draw power from it
+    B,
+}
+
 impl ChargingStatus {
     pub fn new(value: u8) -> Self {
         Self {
             bits: value
         }
     }
-}
\ No newline at end of file
+
+    /// Read which mode the chip is running in.
+    pub fn mode(&self) -> Mode {
+        if self.contains(Self::MODE_B) {
+            Mode::B
+        } else {
+            Mode::A
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Turns raw ADC codes into real units, one function per kind of channel.
+//! They're all worked out from the table in `channel`.
+//!
+//! Every step size here comes straight from the ADC table in the datasheet,
+//! and because they're all whole numbers of micro-somethings (or tenths of a
+//! degree) nothing gets rounded away. The getters on `Axp209` round these to
+//! the nearest milli-whatever, so if you need the extra digits use
+//! `Axp209::read_channel()`, or read the raw code with `Axp209::adc_raw()`
+//! and convert it here:
+//!
+//! ```ignore
+//!     let raw = pmic.adc_raw(Channel::BatteryVoltage).unwrap();
+//!     let voltage = convert::battery_voltage(raw as u16);     // 4184300µV
+//! ```
+
+use channel::Channel;
+use units::{DeciCelsius, MicroAmps, MicroVolts, MicroWatts};
+
+/// Step size of the battery voltage channel
+pub const BATTERY_VOLTAGE_STEP: MicroVolts = MicroVolts(1100);
+/// Step size of both the battery charge and discharge current channels
+pub const BATTERY_CURRENT_STEP: MicroAmps = MicroAmps(500);
+/// Step size of both the ACIN and VBUS voltage channels
+pub const INPUT_VOLTAGE_STEP: MicroVolts = MicroVolts(1700);
+/// Step size of the ACIN current channel
+pub const ACIN_CURRENT_STEP: MicroAmps = MicroAmps(625);
+/// Step size of the VBUS current channel
+pub const VBUS_CURRENT_STEP: MicroAmps = MicroAmps(375);
+/// Step size of the TS pin voltage channel
+pub const TS_VOLTAGE_STEP: MicroVolts = MicroVolts(800);
+/// Step size of the IPSOUT (APS) voltage channel
+pub const IPSOUT_VOLTAGE_STEP: MicroVolts = MicroVolts(1400);
+/// Step size of both GPIO voltage channels
+pub const GPIO_VOLTAGE_STEP: MicroVolts = MicroVolts(500);
+/// What a raw temperature code of zero means
+pub const TEMPERATURE_OFFSET: DeciCelsius = DeciCelsius(-1447);
+
+pub fn battery_voltage(value: u16) -> MicroVolts {
+    MicroVolts(value_of(Channel::BatteryVoltage, value) as u32)
+}
+
+/// Works for both charge and discharge current.
+pub fn battery_current(value: u16) -> MicroAmps {
+    MicroAmps(value_of(Channel::BatteryDischargeCurrent, value) as u32)
+}
+
+/// Works for both ACIN and VBUS.
+pub fn input_voltage(value: u16) -> MicroVolts {
+    MicroVolts(value_of(Channel::AcinVoltage, value) as u32)
+}
+
+pub fn acin_current(value: u16) -> MicroAmps {
+    MicroAmps(value_of(Channel::AcinCurrent, value) as u32)
+}
+
+pub fn vbus_current(value: u16) -> MicroAmps {
+    MicroAmps(value_of(Channel::VbusCurrent, value) as u32)
+}
+
+pub fn temperature(value: u16) -> DeciCelsius {
+    // Check out page 25 of the datasheet for the weird math. Each step
+    // is a tenth of a degree.
+    DeciCelsius(value_of(Channel::Temperature, value) as i16)
+}
+
+pub fn ts_voltage(value: u16) -> MicroVolts {
+    MicroVolts(value_of(Channel::TsVoltage, value) as u32)
+}
+
+/// I'm assuming power division is 1.4 as defined in APS, but as there is
+/// nothing in the datasheet specifically for Ipsout's settings and there
+/// is no register defined for ipsout.
+pub fn ipsout_voltage(value: u16) -> MicroVolts {
+    MicroVolts(value_of(Channel::IpsoutVoltage, value) as u32)
+}
+
+/// Unconfirmed
+pub fn gpio_voltage(value: u16) -> MicroVolts {
+    MicroVolts(value_of(Channel::Gpio0Voltage, value) as u32)
+}
+
+/// This one isn't quite exact as the step is 0.55µW (the voltage step
+/// times the current step), so it's rounded down.
+pub fn battery_power(value: u32) -> MicroWatts {
+    MicroWatts(Channel::BatteryPower.info().value(value) as u32)
+}
+
+fn value_of(channel: Channel, value: u16) -> i32 {
+    channel.info().value(value as u32)
+}
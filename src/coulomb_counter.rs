@@ -0,0 +1,64 @@
+//! The AXP209 keeps a running total of the current going into and out of
+//! the battery (registers 0xB0 - 0xB7), controlled by register 0xB8. It has
+//! to be switched on with `CoulombControl::ENABLE` before it counts anything.
+//!
+//! The counters tick once per ADC sample, so turning them into charge needs
+//! the sample rate from `AdcSampleTs`. `CoulombCounter::net_charge()` does
+//! that for you.
+
+use adc_control::SampleRate;
+use byteorder::{ByteOrder, BigEndian};
+
+bitflags! {
+    /// Holds the state of the register. Changes will need to be committed manually
+    pub struct CoulombControl: u8 {
+        /// Whether the counters are running
+        const ENABLE = 1 << 7;
+        /// Stop counting but keep the totals
+        const PAUSE = 1 << 6;
+        /// Reset both totals to zero. Clears itself once it's done.
+        const CLEAR = 1 << 5;
+    }
+}
+
+impl CoulombControl {
+    /// No checks are made here, and it's expected that it be populated by
+    /// the raw value from the axp209 chip.
+    pub fn new(value: u8) -> Self {
+        Self {
+            bits: value
+        }
+    }
+}
+
+/// The raw charge and discharge totals
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct CoulombCounter {
+    pub charge: u32,
+    pub discharge: u32,
+}
+
+impl CoulombCounter {
+    /// Build from the eight raw registers, starting at 0xB0
+    pub fn new(bytes: &[u8; 8]) -> Self {
+        CoulombCounter {
+            charge: BigEndian::read_u32(&bytes[0..4]),
+            discharge: BigEndian::read_u32(&bytes[4..8]),
+        }
+    }
+
+    /// Charge that went in minus charge that came out, in microamp hours.
+    /// Negative means the battery has given more than it got.
+    pub fn net_charge(&self, rate: SampleRate) -> i64 {
+        let hz: i64 = match rate {
+            SampleRate::Hz25 => 25,
+            SampleRate::Hz50 => 50,
+            SampleRate::Hz100 => 100,
+            SampleRate::Hz200 => 200,
+        };
+        let counts = self.charge as i64 - self.discharge as i64;
+
+        // From the datasheet: 65536 * 0.5mA * counts / 3600 / sample rate
+        counts * 65536 * 500 / 3600 / hz
+    }
+}
@@ -0,0 +1,102 @@
+//! The AXP209 has 12 bytes of data buffer (registers 0x04 through 0x0F) that
+//! keep their value as long as the chip has power, including from the backup
+//! battery. That makes them handy for remembering things across reboots
+//! without having to write to flash, like how many times the system has
+//! crashed.
+//!
+//! `Axp209::read_buffer()` and `Axp209::write_buffer()` give raw access to
+//! the bytes. If you'd rather not invent your own format, implement
+//! `BufferRecord` for your type and use `Axp209::load_record()` and
+//! `Axp209::store_record()`, which store it like so:
+//!
+//! ```text
+//! | 0       | 1 .. 10 | 11       |
+//! | version | payload | checksum |
+//! ```
+//!
+//! Only one record fits, so everyone sharing the buffer needs to agree on
+//! what's in it. Here's a crash counter:
+//!
+//! ```ignore
+//!     let i2c = I2cdev::new("/dev/i2c-0").unwrap();
+//!     let mut pmic = Axp209::new(i2c);
+//!
+//!     let mut counter = pmic.load_record::<BootCounter>().unwrap().unwrap_or_default();
+//!     counter.count += 1;
+//!     pmic.store_record(&counter).unwrap();
+//! ```
+
+use byteorder::{ByteOrder, BigEndian};
+
+/// Number of bytes in the data buffer
+pub const DATA_BUFFER_SIZE: usize = 12;
+/// Number of bytes a `BufferRecord` has to itself
+pub const RECORD_PAYLOAD_SIZE: usize = DATA_BUFFER_SIZE - 2;
+
+/// Starting value for the checksum so a cleared buffer doesn't pass
+const CHECKSUM_SEED: u8 = 0xa5;
+
+/// Something that can be kept in the data buffer.
+pub trait BufferRecord: Sized {
+    /// Stored alongside the payload. Bump it whenever the layout changes so
+    /// old data isn't misread. Zero is reserved for "nothing stored".
+    const VERSION: u8;
+
+    /// Write the record into the payload bytes.
+    fn encode(&self, payload: &mut [u8; RECORD_PAYLOAD_SIZE]);
+
+    /// Read the record back out of the payload bytes.
+    fn decode(payload: &[u8; RECORD_PAYLOAD_SIZE]) -> Self;
+}
+
+/// Pack a record into a full buffer, version and checksum included.
+pub fn encode_record<T: BufferRecord>(record: &T) -> [u8; DATA_BUFFER_SIZE] {
+    let mut payload = [0u8; RECORD_PAYLOAD_SIZE];
+    let mut buf = [0u8; DATA_BUFFER_SIZE];
+
+    record.encode(&mut payload);
+
+    buf[0] = T::VERSION;
+    buf[1..DATA_BUFFER_SIZE - 1].copy_from_slice(&payload);
+    buf[DATA_BUFFER_SIZE - 1] = checksum(&buf[..DATA_BUFFER_SIZE - 1]);
+
+    buf
+}
+
+/// Unpack a record from a full buffer. Returns `None` if the buffer holds
+/// a different version or the checksum doesn't match, which is what you'll
+/// get after the backup battery runs flat.
+pub fn decode_record<T: BufferRecord>(buf: &[u8; DATA_BUFFER_SIZE]) -> Option<T> {
+    if buf[0] != T::VERSION || buf[DATA_BUFFER_SIZE - 1] != checksum(&buf[..DATA_BUFFER_SIZE - 1]) {
+        return None;
+    }
+
+    let mut payload = [0u8; RECORD_PAYLOAD_SIZE];
+    payload.copy_from_slice(&buf[1..DATA_BUFFER_SIZE - 1]);
+
+    Some(T::decode(&payload))
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(CHECKSUM_SEED, |sum, x| sum.rotate_left(1) ^ x)
+}
+
+/// A ready-made record counting boots (or crashes, or whatever you like).
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct BootCounter {
+    pub count: u32,
+}
+
+impl BufferRecord for BootCounter {
+    const VERSION: u8 = 1;
+
+    fn encode(&self, payload: &mut [u8; RECORD_PAYLOAD_SIZE]) {
+        BigEndian::write_u32(&mut payload[0..4], self.count);
+    }
+
+    fn decode(payload: &[u8; RECORD_PAYLOAD_SIZE]) -> Self {
+        BootCounter {
+            count: BigEndian::read_u32(&payload[0..4]),
+        }
+    }
+}
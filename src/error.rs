@@ -0,0 +1,35 @@
+use channel::Channel;
+use rail::Rail;
+use rail_policy::PolicyViolation;
+use units::MilliVolts;
+
+/// Everything that can go wrong talking to the chip. `E` is the error type
+/// of the I2C implementation you gave to `Axp209::new()`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Error<E> {
+    /// The I2C bus had a problem
+    I2c(E),
+    /// The ADC channel is switched off in `AdcControl`, so its value would
+    /// be stale. Only returned in strict mode (see `Axp209::set_strict()`).
+    ChannelDisabled(Channel),
+    /// A register read back different to what was just written to it, so
+    /// the write only partly happened, or not at all
+    VerifyFailed { register: u8, wrote: u8, read: u8 },
+    /// The write would break the rail policy, so it wasn't done (see
+    /// `Axp209::with_rail_policy()`)
+    RailPolicy(PolicyViolation),
+    /// The rail can't be set to that voltage at all (see `rail` for the
+    /// ranges)
+    VoltageOutOfRange(Rail, MilliVolts),
+    /// GPIO0 can't be set to that voltage as an LDO
+    GpioVoltageOutOfRange(MilliVolts),
+    /// The rail doesn't have a voltage to set, which is EXTEN
+    NoVoltage(Rail),
+    /// Every consumer slot for the rail is taken (see `regulator`)
+    NoConsumerSlots(Rail),
+    /// Consumers want voltages with nothing in common (see `regulator`)
+    VoltageConflict(Rail),
+    /// The register can't be written through the cache (see
+    /// `cache::is_cacheable()`)
+    NotCacheable(u8),
+}
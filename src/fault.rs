@@ -0,0 +1,289 @@
+//! A wrapper around any I2C bus that breaks it on purpose. Real buses NAK,
+//! lose arbitration to another master and hand back garbage now and then,
+//! and this is how to check the driver (and whatever is built on it) copes
+//! with that:
+//!
+//! ```ignore
+//! let mut bus = Faulty::new(Sim::new());
+//! // The third read of the battery level gets a NAK, once
+//! bus.inject(Fault::new(FaultKind::Nak).on_register(0xb9).after(2).times(1));
+//!
+//! let mut pmic = Axp209::new(bus);
+//! ```
+//!
+//! Faults are matched against the registers a transaction touches, which is
+//! worked out from the register address at the start of each write, so a
+//! burst read of the data buffer counts as touching all 12 registers. When
+//! more than one fault matches, the first one injected wins.
+//!
+//! Only built with the `sim` feature (and for the crate's own tests).
+
+use hal::blocking::i2c::{Read, Write, WriteRead};
+
+/// How many faults can be lined up at once
+pub const MAX_FAULTS: usize = 8;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum FaultError<E> {
+    /// The wrapped bus went wrong by itself
+    Bus(E),
+    /// The device didn't acknowledge
+    Nak,
+    /// Another master won the bus
+    ArbitrationLoss,
+}
+
+/// What goes wrong
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum FaultKind {
+    /// Fail the transaction with `FaultError::Nak`
+    Nak,
+    /// Fail the transaction with `FaultError::ArbitrationLoss`
+    ArbitrationLoss,
+    /// Only this many bytes of a read come back, the rest read as 0xff like
+    /// a bus nobody is driving
+    ShortRead(usize),
+    /// Flip these bits in the register's value when it's read
+    BitFlip(u8),
+    /// The register always reads as this
+    Stuck(u8),
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Fault {
+    kind: FaultKind,
+    /// Only transactions touching this register, or all of them
+    register: Option<u8>,
+    /// How many matching transactions to let through first
+    after: usize,
+    /// How many times to strike before going away
+    times: usize,
+}
+
+impl Fault {
+    /// A fault that hits every transaction, forever. Narrow it down with
+    /// the other methods.
+    pub fn new(kind: FaultKind) -> Self {
+        Fault {
+            kind,
+            register: None,
+            after: 0,
+            times: usize::MAX,
+        }
+    }
+
+    /// Only hit transactions touching `register`
+    pub fn on_register(mut self, register: u8) -> Self {
+        self.register = Some(register);
+        self
+    }
+
+    /// Let `count` matching transactions through before striking
+    pub fn after(mut self, count: usize) -> Self {
+        self.after = count;
+        self
+    }
+
+    /// Strike `count` times and then go away. Zero means it's used up
+    /// before it starts, and never strikes at all.
+    pub fn times(mut self, count: usize) -> Self {
+        self.times = count;
+        self
+    }
+
+    fn matches(&self, start: u8, len: usize, reading: bool) -> bool {
+        let fails = matches!(self.kind, FaultKind::Nak | FaultKind::ArbitrationLoss);
+
+        // Writes don't have a value to mess with
+        if !reading && !fails {
+            return false;
+        }
+
+        match self.register {
+            Some(x) => (x.wrapping_sub(start) as usize) < len.max(1),
+            None => true,
+        }
+    }
+}
+
+pub struct Faulty<I2C> {
+    bus: I2C,
+    faults: [Option<Fault>; MAX_FAULTS],
+    /// The register address of the last write, which is where reads start
+    pointer: u8,
+}
+
+impl<I2C> Faulty<I2C> {
+    pub fn new(bus: I2C) -> Self {
+        Faulty {
+            bus,
+            faults: [None; MAX_FAULTS],
+            pointer: 0,
+        }
+    }
+
+    /// Line up a fault. Panics if there are already `MAX_FAULTS` of them.
+    pub fn inject(&mut self, fault: Fault) {
+        if fault.times == 0 {
+            return;
+        }
+
+        match self.faults.iter_mut().find(|x| x.is_none()) {
+            Some(slot) => *slot = Some(fault),
+            None => panic!("Can't have more than {} faults", MAX_FAULTS),
+        }
+    }
+
+    /// Get rid of every fault, whether it struck or not
+    pub fn clear(&mut self) {
+        self.faults = [None; MAX_FAULTS];
+    }
+
+    /// Whether every fault has run its course
+    pub fn is_spent(&self) -> bool {
+        self.faults.iter().all(|x| x.is_none())
+    }
+
+    pub fn into_inner(self) -> I2C {
+        self.bus
+    }
+
+    /// Find the fault to apply to a transaction over `len` registers from
+    /// `start`, counting it against every fault that matches. Hands back the
+    /// register it was aimed at too, if any.
+    fn strike(&mut self, start: u8, len: usize, reading: bool) -> Option<(FaultKind, Option<u8>)> {
+        let mut struck = None;
+
+        for slot in self.faults.iter_mut() {
+            let fault = match slot {
+                Some(x) if x.matches(start, len, reading) => x,
+                _ => continue,
+            };
+
+            if fault.after > 0 {
+                fault.after -= 1;
+                continue;
+            }
+
+            if struck.is_none() {
+                struck = Some((fault.kind, fault.register));
+                fault.times -= 1;
+
+                if fault.times == 0 {
+                    *slot = None;
+                }
+            }
+        }
+
+        struck
+    }
+}
+
+/// Mess up the bytes of a read that started at register `start`
+fn corrupt(kind: FaultKind, register: Option<u8>, start: u8, buffer: &mut [u8]) {
+    // Which bytes the fault covers
+    let range = match register {
+        Some(x) => {
+            let offset = x.wrapping_sub(start) as usize;
+            offset..offset + 1
+        },
+        None => 0..buffer.len(),
+    };
+
+    match kind {
+        FaultKind::ShortRead(count) => {
+            for x in buffer.iter_mut().skip(count) {
+                *x = 0xff;
+            }
+        },
+        FaultKind::BitFlip(mask) => {
+            for x in &mut buffer[range] {
+                *x ^= mask;
+            }
+        },
+        FaultKind::Stuck(value) => {
+            for x in &mut buffer[range] {
+                *x = value;
+            }
+        },
+        FaultKind::Nak | FaultKind::ArbitrationLoss => {},
+    }
+}
+
+/// The faults that fail a transaction outright
+fn failure<E>(kind: FaultKind) -> Result<(), FaultError<E>> {
+    match kind {
+        FaultKind::Nak => Err(FaultError::Nak),
+        FaultKind::ArbitrationLoss => Err(FaultError::ArbitrationLoss),
+        _ => Ok(()),
+    }
+}
+
+impl<I2C, E> Write for Faulty<I2C>
+where
+    I2C: Write<Error = E>,
+{
+    type Error = FaultError<E>;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        if let Some(x) = bytes.first() {
+            self.pointer = *x;
+        }
+
+        if let Some((kind, _)) = self.strike(self.pointer, bytes.len().saturating_sub(1), false) {
+            failure(kind)?;
+        }
+
+        self.bus.write(address, bytes).map_err(FaultError::Bus)
+    }
+}
+
+impl<I2C, E> Read for Faulty<I2C>
+where
+    I2C: Read<Error = E>,
+{
+    type Error = FaultError<E>;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let start = self.pointer;
+        let struck = self.strike(start, buffer.len(), true);
+
+        if let Some((kind, _)) = struck {
+            failure(kind)?;
+        }
+
+        self.bus.read(address, buffer).map_err(FaultError::Bus)?;
+        self.pointer = start.wrapping_add(buffer.len() as u8);
+
+        if let Some((kind, register)) = struck {
+            corrupt(kind, register, start, buffer);
+        }
+
+        Ok(())
+    }
+}
+
+impl<I2C, E> WriteRead for Faulty<I2C>
+where
+    I2C: WriteRead<Error = E>,
+{
+    type Error = FaultError<E>;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let start = bytes.first().cloned().unwrap_or(self.pointer);
+        let struck = self.strike(start, buffer.len(), true);
+
+        if let Some((kind, _)) = struck {
+            failure(kind)?;
+        }
+
+        self.bus.write_read(address, bytes, buffer).map_err(FaultError::Bus)?;
+        self.pointer = start.wrapping_add(buffer.len() as u8);
+
+        if let Some((kind, register)) = struck {
+            corrupt(kind, register, start, buffer);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,361 @@
+//! A state of charge estimator that does better than the chip's built-in
+//! `battery_level()`, which is known to jump around.
+//!
+//! It counts charge in and out with the coulomb counter, which is smooth but
+//! slowly drifts, and corrects itself against the open circuit voltage of the
+//! cell whenever the battery has been resting long enough for the voltage to
+//! mean something. A full charge resets it to 100%, and discharging from full
+//! down to a known low point teaches it the real capacity of the battery.
+//!
+//! It needs to be fed regularly, about once a minute is plenty:
+//!
+//! ```ignore
+//!     let mut gauge = match pmic.load_record::<GaugeState>().unwrap() {
+//!         Some(x) => FuelGauge::resume(x, &LIPO_OCV).unwrap(),
+//!         None => FuelGauge::new(MilliAmpHours(2000), &LIPO_OCV).unwrap(),
+//!     };
+//!
+//!     loop {
+//!         let estimate = pmic.update_fuel_gauge(&mut gauge).unwrap();
+//!         println!("{}% ({}% sure)", estimate.level, estimate.confidence);
+//!
+//!         pmic.store_record(&gauge.state()).unwrap();
+//!         // Wait a minute
+//!     }
+//! ```
+//!
+//! The coulomb counter has to be switched on for this to work, see
+//! `coulomb_counter`.
+
+use byteorder::{ByteOrder, BigEndian};
+
+use data_buffer::{BufferRecord, RECORD_PAYLOAD_SIZE};
+use units::{MilliAmpHours, MilliAmps, MilliVolts};
+
+/// One point on an open circuit voltage curve
+#[derive(Debug, Clone, Copy)]
+pub struct OcvPoint {
+    pub voltage: MilliVolts,
+    /// In percent
+    pub level: u8,
+}
+
+/// A typical curve for a single lithium polymer or lithium ion cell
+pub const LIPO_OCV: [OcvPoint; 12] = [
+    OcvPoint { voltage: MilliVolts(3000), level: 0 },
+    OcvPoint { voltage: MilliVolts(3450), level: 5 },
+    OcvPoint { voltage: MilliVolts(3600), level: 10 },
+    OcvPoint { voltage: MilliVolts(3680), level: 20 },
+    OcvPoint { voltage: MilliVolts(3730), level: 30 },
+    OcvPoint { voltage: MilliVolts(3770), level: 40 },
+    OcvPoint { voltage: MilliVolts(3800), level: 50 },
+    OcvPoint { voltage: MilliVolts(3840), level: 60 },
+    OcvPoint { voltage: MilliVolts(3900), level: 70 },
+    OcvPoint { voltage: MilliVolts(3970), level: 80 },
+    OcvPoint { voltage: MilliVolts(4060), level: 90 },
+    OcvPoint { voltage: MilliVolts(4180), level: 100 },
+];
+
+/// Below this current in both directions the battery counts as resting
+const REST_CURRENT: MilliAmps = MilliAmps(20);
+/// How many resting updates in a row before the voltage is trusted
+const REST_UPDATES: u8 = 10;
+/// The coulomb counter is assumed to be off by one part in this many
+const COULOMB_DRIFT: i64 = 50;
+/// How far off (in percent of capacity) an open circuit voltage reading can be
+const OCV_ERROR: i64 = 5;
+/// A battery the charger has stopped on is only counted as full above this
+pub const FULL_VOLTAGE: MilliVolts = MilliVolts(4100);
+/// What the coulomb reading is saved as when there isn't one, which is the
+/// lowest a 24 bit number goes
+const NO_COULOMB: i32 = -0x80_0000;
+/// How far off (in percent of capacity) a full charge can be
+const FULL_ERROR: i64 = 1;
+/// Capacity is only learned from a discharge at least this big, in percent
+const LEARN_MIN_DEPTH: i64 = 50;
+
+/// Why a gauge couldn't be made
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum GaugeError {
+    /// A battery that holds nothing, which is usually a zeroed out
+    /// `GaugeState`
+    NoCapacity,
+    /// The OCV table is empty, or its voltages don't go up from one point to
+    /// the next, or its levels go down or past 100%
+    BadCurve,
+}
+
+/// What the gauge needs to know each update
+#[derive(Debug, Clone, Copy)]
+pub struct GaugeSample {
+    /// `CoulombCounter::net_charge()`, in microamp hours
+    pub coulomb: i64,
+    pub voltage: MilliVolts,
+    pub charging_current: MilliAmps,
+    pub discharging_current: MilliAmps,
+    /// Whether the charger has finished (power is in, battery is there, but
+    /// it's not charging any more and the voltage is over `FULL_VOLTAGE`)
+    pub full: bool,
+}
+
+/// The gauge's best guess
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Estimate {
+    /// In percent
+    pub level: u8,
+    /// How much to trust `level`, in percent
+    pub confidence: u8,
+    pub remaining: MilliAmpHours,
+    pub capacity: MilliAmpHours,
+}
+
+/// Everything the gauge needs to pick up where it left off. It fits in the
+/// chip's data buffer (see `BufferRecord`), or `data_buffer::encode_record()`
+/// turns it into bytes for saving anywhere else.
+///
+/// To fit, the coulomb counter reading is only kept to the nearest milliamp
+/// hour in the data buffer. That's a few counts of the counter itself, so
+/// not much is lost.
+///
+/// A gauge resumed without a coulomb reading only takes note of the counter
+/// on its first update, rather than counting everything on it as charge
+/// that's come and gone.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct GaugeState {
+    pub remaining: MilliAmpHours,
+    /// What's been learned so far
+    pub capacity: MilliAmpHours,
+    /// What the gauge was made with, which learning is kept close to
+    pub design_capacity: MilliAmpHours,
+    /// In percent
+    pub confidence: u8,
+    /// The last coulomb counter reading, in microamp hours, if there's been
+    /// one
+    pub coulomb: Option<i32>,
+}
+
+impl BufferRecord for GaugeState {
+    const VERSION: u8 = 0x12;
+
+    fn encode(&self, payload: &mut [u8; RECORD_PAYLOAD_SIZE]) {
+        BigEndian::write_u16(&mut payload[0..2], self.remaining.0);
+        BigEndian::write_u16(&mut payload[2..4], self.capacity.0);
+        BigEndian::write_u16(&mut payload[4..6], self.design_capacity.0);
+        payload[6] = self.confidence;
+        BigEndian::write_i24(&mut payload[7..10], self.coulomb.map_or(NO_COULOMB, |x| (x / 1000).max(NO_COULOMB + 1)));
+    }
+
+    fn decode(payload: &[u8; RECORD_PAYLOAD_SIZE]) -> Self {
+        GaugeState {
+            remaining: MilliAmpHours(BigEndian::read_u16(&payload[0..2])),
+            capacity: MilliAmpHours(BigEndian::read_u16(&payload[2..4])),
+            design_capacity: MilliAmpHours(BigEndian::read_u16(&payload[4..6])),
+            confidence: payload[6],
+            coulomb: match BigEndian::read_i24(&payload[7..10]) {
+                NO_COULOMB => None,
+                x => Some(x * 1000),
+            },
+        }
+    }
+}
+
+pub struct FuelGauge<'a> {
+    ocv: &'a [OcvPoint],
+    /// What the battery is supposed to hold, for sanity checking what's learned
+    design_capacity: i64,
+    /// All the charges here are in microamp hours
+    capacity: i64,
+    remaining: i64,
+    /// How far off `remaining` might be
+    uncertainty: i64,
+    /// The last coulomb counter reading, if there's been one
+    coulomb: Option<i64>,
+    /// Whether there's an estimate yet, from an update or a saved state
+    estimated: bool,
+    /// The coulomb counter reading when the battery was last full
+    full_mark: Option<i64>,
+    rest_count: u8,
+}
+
+impl<'a> FuelGauge<'a> {
+    /// Start from scratch. The first estimate will be a guess from the
+    /// battery voltage and confidence stays low until the battery gets a
+    /// chance to rest.
+    pub fn new(design_capacity: MilliAmpHours, ocv: &'a [OcvPoint]) -> Result<Self, GaugeError> {
+        if design_capacity.0 == 0 {
+            return Err(GaugeError::NoCapacity);
+        }
+        if !is_valid_curve(ocv) {
+            return Err(GaugeError::BadCurve);
+        }
+
+        let capacity = design_capacity.0 as i64 * 1000;
+
+        Ok(FuelGauge {
+            ocv,
+            design_capacity: capacity,
+            capacity,
+            remaining: capacity / 2,
+            uncertainty: capacity,
+            coulomb: None,
+            estimated: false,
+            full_mark: None,
+            rest_count: 0,
+        })
+    }
+
+    /// Pick up from a saved state. A learned capacity that's strayed too far
+    /// from the design capacity is put back to the design capacity.
+    pub fn resume(state: GaugeState, ocv: &'a [OcvPoint]) -> Result<Self, GaugeError> {
+        let mut gauge = FuelGauge::new(state.design_capacity, ocv)?;
+        let capacity = state.capacity.0 as i64 * 1000;
+
+        if gauge.is_plausible(capacity) {
+            gauge.capacity = capacity;
+        }
+
+        gauge.remaining = (state.remaining.0 as i64 * 1000).min(gauge.capacity);
+        gauge.uncertainty = gauge.capacity * (100 - state.confidence.min(100) as i64) / 100;
+        gauge.coulomb = state.coulomb.map(|x| x as i64);
+        gauge.estimated = true;
+
+        Ok(gauge)
+    }
+
+    /// Something to save so the gauge survives a restart
+    pub fn state(&self) -> GaugeState {
+        GaugeState {
+            remaining: MilliAmpHours((self.remaining / 1000) as u16),
+            capacity: MilliAmpHours((self.capacity / 1000) as u16),
+            design_capacity: MilliAmpHours((self.design_capacity / 1000) as u16),
+            confidence: self.confidence(),
+            coulomb: self.coulomb.map(|x| x as i32),
+        }
+    }
+
+    pub fn estimate(&self) -> Estimate {
+        Estimate {
+            level: (self.remaining * 100 / self.capacity) as u8,
+            confidence: self.confidence(),
+            remaining: MilliAmpHours((self.remaining / 1000) as u16),
+            capacity: MilliAmpHours((self.capacity / 1000) as u16),
+        }
+    }
+
+    fn confidence(&self) -> u8 {
+        (100 - (self.uncertainty * 100 / self.capacity).min(100)) as u8
+    }
+
+    /// Feed in a new set of readings
+    pub fn update(&mut self, sample: &GaugeSample) -> Estimate {
+        let first = !self.estimated;
+        self.estimated = true;
+
+        // Coulomb counting first. More than a whole battery's worth since
+        // last time means the counter was cleared, so start counting again
+        // from here.
+        if let Some(last) = self.coulomb {
+            let delta = sample.coulomb - last;
+
+            if delta.abs() <= self.capacity {
+                self.remaining += delta;
+                self.uncertainty += delta.abs() / COULOMB_DRIFT;
+            }
+        }
+        self.coulomb = Some(sample.coulomb);
+
+        if sample.charging_current < REST_CURRENT && sample.discharging_current < REST_CURRENT {
+            self.rest_count = self.rest_count.saturating_add(1);
+        } else {
+            self.rest_count = 0;
+        }
+
+        if sample.full {
+            self.full_mark = Some(sample.coulomb);
+            self.remaining = self.capacity;
+            self.uncertainty = self.capacity * FULL_ERROR / 100;
+        } else if first || self.rest_count >= REST_UPDATES {
+            // With nothing else to go on, even a voltage under load beats
+            // no idea at all
+            self.rest_count = 0;
+            self.correct_from_voltage(sample);
+        }
+
+        self.remaining = self.remaining.max(0).min(self.capacity);
+        self.uncertainty = self.uncertainty.min(self.capacity);
+
+        self.estimate()
+    }
+
+    /// Blend in what the open circuit voltage says, weighted by how much
+    /// each of the two is trusted.
+    fn correct_from_voltage(&mut self, sample: &GaugeSample) {
+        let level = ocv_level(self.ocv, sample.voltage) as i64;
+        let ocv_remaining = self.capacity * level / 100;
+        let ocv_uncertainty = self.capacity * OCV_ERROR / 100;
+
+        // Discharged deep enough since the last full charge to learn from
+        if let Some(full) = self.full_mark {
+            if level <= 100 - LEARN_MIN_DEPTH {
+                let used = full - sample.coulomb;
+                let learned = used * 100 / (100 - level);
+
+                if self.is_plausible(learned) {
+                    self.capacity = (self.capacity * 3 + learned) / 4;
+                    self.remaining = self.capacity - used;
+                }
+                self.full_mark = None;
+            }
+        }
+
+        let total = self.uncertainty + ocv_uncertainty;
+        self.remaining = (self.remaining * ocv_uncertainty + ocv_remaining * self.uncertainty) / total;
+        self.uncertainty = self.uncertainty * ocv_uncertainty / total;
+    }
+
+    /// Whether a capacity is close enough to the design capacity to believe
+    fn is_plausible(&self, capacity: i64) -> bool {
+        capacity > self.design_capacity / 2 && capacity < self.design_capacity * 3 / 2
+    }
+}
+
+/// Whether `ocv_level()` can make sense of a curve
+fn is_valid_curve(ocv: &[OcvPoint]) -> bool {
+    !ocv.is_empty()
+        && ocv.iter().all(|x| x.level <= 100)
+        && ocv.windows(2).all(|x| x[0].voltage < x[1].voltage && x[0].level <= x[1].level)
+}
+
+/// Look a resting voltage up on the curve, in percent. The curve's voltages
+/// and levels have to go up from one point to the next, and an empty curve
+/// knows nothing so it gives 0%.
+pub fn ocv_level(ocv: &[OcvPoint], voltage: MilliVolts) -> u8 {
+    let (first, last) = match (ocv.first(), ocv.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return 0,
+    };
+
+    if voltage <= first.voltage {
+        return first.level;
+    }
+    if voltage >= last.voltage {
+        return last.level;
+    }
+
+    for pair in ocv.windows(2) {
+        let (low, high) = (pair[0], pair[1]);
+
+        // Signed, so a curve that doesn't go up gives a wrong answer rather
+        // than a panic
+        if voltage <= high.voltage && voltage > low.voltage {
+            let span = high.voltage.0 as i32 - low.voltage.0 as i32;
+            let into = voltage.0 as i32 - low.voltage.0 as i32;
+            let levels = high.level as i32 - low.level as i32;
+            let level = low.level as i32 + into * levels / span;
+
+            return level.clamp(0, 100) as u8;
+        }
+    }
+
+    last.level
+}
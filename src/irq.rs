@@ -0,0 +1,129 @@
+//! The AXP209 latches interesting events (power plugged in, key pressed,
+//! timer expired, ...) into five IRQ status registers (0x48 - 0x4C), and
+//! has five matching enable registers (0x40 - 0x44) deciding which of them
+//! pull the IRQ pin low.
+//!
+//! Here all five banks are squashed into one `Irq` value. The status bits
+//! stay set until they're cleared by writing a one back to them, which is
+//! what `Axp209::clear_irq()` does. Status bits are latched whether the
+//! matching enable bit is set or not, so you can poll for events without
+//! wiring up the IRQ pin at all.
+
+/// Number of IRQ registers in each of the enable and status banks
+pub const IRQ_BANKS: usize = 5;
+
+bitflags! {
+    /// Each constant is `1 << (bank * 8 + bit)` where bank zero is register
+    /// 0x40 (enable) or 0x48 (status).
+    pub struct Irq: u64 {
+        // Bank 1 (0x40 / 0x48)
+        /// ACIN went over-voltage
+        const ACIN_OVER_VOLTAGE = 1 << 7;
+        /// ACIN was plugged in
+        const ACIN_PLUGGED = 1 << 6;
+        /// ACIN was removed
+        const ACIN_REMOVED = 1 << 5;
+        /// VBUS went over-voltage
+        const VBUS_OVER_VOLTAGE = 1 << 4;
+        /// VBUS was plugged in
+        const VBUS_PLUGGED = 1 << 3;
+        /// VBUS was removed
+        const VBUS_REMOVED = 1 << 2;
+        /// VBUS dropped below VHOLD
+        const VBUS_BELOW_HOLD = 1 << 1;
+
+        // Bank 2 (0x41 / 0x49)
+        /// A battery was connected
+        const BATTERY_CONNECTED = 1 << 15;
+        /// The battery was removed
+        const BATTERY_REMOVED = 1 << 14;
+        /// The battery entered activation mode
+        const BATTERY_ACTIVATION_ENTERED = 1 << 13;
+        /// The battery left activation mode
+        const BATTERY_ACTIVATION_EXITED = 1 << 12;
+        /// Charging started
+        const CHARGING = 1 << 11;
+        /// Charging finished
+        const CHARGING_DONE = 1 << 10;
+        /// The battery went over its temperature limit
+        const BATTERY_OVERTEMPERATURE = 1 << 9;
+        /// The battery went under its temperature limit
+        const BATTERY_UNDERTEMPERATURE = 1 << 8;
+
+        // Bank 3 (0x42 / 0x4A)
+        /// The chip itself is over temperature
+        const OVERTEMPERATURE = 1 << 23;
+        /// The charge current is less than what was configured
+        const CHARGE_CURRENT_LOW = 1 << 22;
+        /// The DCDC2 output voltage is too low
+        const DCDC2_UNDER_VOLTAGE = 1 << 20;
+        /// The DCDC3 output voltage is too low
+        const DCDC3_UNDER_VOLTAGE = 1 << 19;
+        /// The LDO3 output voltage is too low
+        const LDO3_UNDER_VOLTAGE = 1 << 18;
+        /// The power key was pressed briefly
+        const PEK_SHORT_PRESS = 1 << 17;
+        /// The power key was held down
+        const PEK_LONG_PRESS = 1 << 16;
+
+        // Bank 4 (0x43 / 0x4B)
+        /// N_OE asked for the system to power on
+        const NOE_POWER_ON = 1 << 31;
+        /// N_OE asked for the system to power off
+        const NOE_POWER_OFF = 1 << 30;
+        /// VBUS became valid (see `OtgVbusStatus`)
+        const VBUS_VALID = 1 << 29;
+        /// VBUS stopped being valid
+        const VBUS_INVALID = 1 << 28;
+        /// VBUS session A/B became valid
+        const VBUS_SESSION_VALID = 1 << 27;
+        /// VBUS session ended
+        const VBUS_SESSION_END = 1 << 26;
+        /// IPSOUT dropped below warning level 1
+        const APS_LOW_LEVEL1 = 1 << 25;
+        /// IPSOUT dropped below warning level 2
+        const APS_LOW_LEVEL2 = 1 << 24;
+
+        // Bank 5 (0x44 / 0x4C)
+        /// The countdown timer expired (see `timer_control`)
+        const TIMER = 1 << 39;
+        /// The power key was pressed down
+        const PEK_PRESSED = 1 << 38;
+        /// The power key was let go
+        const PEK_RELEASED = 1 << 37;
+        /// Edge on GPIO3
+        const GPIO3 = 1 << 35;
+        /// Edge on GPIO2
+        const GPIO2 = 1 << 34;
+        /// Edge on GPIO1, or the GPIO1 ADC crossed its threshold
+        const GPIO1 = 1 << 33;
+        /// Edge on GPIO0, or the GPIO0 ADC crossed its threshold
+        const GPIO0 = 1 << 32;
+    }
+}
+
+impl Irq {
+    /// Build the value from the five raw registers, lowest address first.
+    pub fn from_banks(banks: &[u8; IRQ_BANKS]) -> Self {
+        let mut bits: u64 = 0;
+
+        for (i, bank) in banks.iter().enumerate() {
+            bits |= (*bank as u64) << (i * 8);
+        }
+
+        Self {
+            bits
+        }
+    }
+
+    /// Split the value back into the five raw registers, lowest address first.
+    pub fn to_banks(&self) -> [u8; IRQ_BANKS] {
+        let mut banks = [0u8; IRQ_BANKS];
+
+        for (i, bank) in banks.iter_mut().enumerate() {
+            *bank = (self.bits >> (i * 8)) as u8;
+        }
+
+        banks
+    }
+}
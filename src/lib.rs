@@ -31,6 +31,33 @@
 //!   * Is that battery (dis)charging?
 //! * Using the internal 127 minute timer (see `timer_control`)
 //! * Turning various output voltages on and off
+//! * Setting output voltages, with an optional policy protecting critical rails
+//!   (see `rail_policy`)
+//! * Changing part of a register with `modify_*()` closures, which leave the
+//!   bits this crate doesn't know about alone
+//! * Profiles of which rail powers what on known boards (see `boards`)
+//! * Sharing rails between consumers with reference counting (see `regulator`)
+//! * Ordered power up and down sequences with rollback (see `sequence`)
+//! * Configuring the over-temperature shutdown
+//! * Storing data across reboots in the 12 byte data buffer
+//! * Retrying on a flaky I2C bus (see `retry`)
+//! * Typed units (`MilliVolts`, `MilliAmps`, ...) that convert into `uom` quantities
+//!   with the `uom` feature
+//! * An in-memory simulated chip, a scripted mock bus and a fault injecting bus
+//!   wrapper for testing without hardware, with the `sim` feature (see `sim`,
+//!   `mock` and `fault`)
+//! * `axp209ctl`, a command line tool for Linux boards covering status, ADC
+//!   readings, rails, charging, the timer, IRQs and register dumps, with the
+//!   `cli` feature
+//! 
+//! Every register this crate can write has a `modify_*()`: ADC control,
+//! ADC sample rate/TS, power control, the rail voltages, the timer,
+//! over-temperature, IRQ enable, charge control and coulomb control. The
+//! GPIO LDO setters only ever touch their own bits. The other writable
+//! registers (charge control 2, the other GPIO controls, the input path
+//! and shutdown settings and so on) don't have a type here yet, and the
+//! only thing that writes them is `restore_registers()`, which puts back
+//! whole values that were read from the chip in the first place.
 //! 
 //! Here's the output from the example program which runs on the PocketChip:
 //! 
@@ -43,7 +70,7 @@
 //! ACIN Current:      0mA
 //! Vbus Voltage:      0mV
 //! Vbus Current:      0mA
-//! Temperature:       47°C
+//! Temperature:       47.3°C
 //! Temp Sensor Pin:   0mV
 //! Ipsout?!:          4583mV
 //! 
@@ -75,22 +102,82 @@ extern crate embedded_hal as hal;
 #[macro_use]
 extern crate bitflags;
 extern crate byteorder;
+#[cfg(feature = "uom")]
+extern crate uom;
 
 pub mod adc_control;
 pub mod power_status;
 pub mod power_control;
 pub mod charging_status;
+pub mod otg_vbus_status;
 pub mod timer_control;
-
-pub use self::adc_control::{AdcControl, AdcSampleTs, SampleRate};
+pub mod over_temperature;
+pub mod data_buffer;
+pub mod irq;
+pub mod boot_reason;
+pub mod register_dump;
+pub mod cache;
+pub mod adc_snapshot;
+pub mod units;
+pub mod convert;
+pub mod channel;
+pub mod error;
+pub mod coulomb_counter;
+pub mod fuel_gauge;
+pub mod retry;
+pub mod rail;
+pub mod rail_policy;
+pub mod boards;
+pub mod regulator;
+pub mod sequence;
+pub mod charge_control;
+pub mod battery_time;
+pub mod battery_state;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+#[cfg(any(test, feature = "sim"))]
+pub mod mock;
+#[cfg(any(test, feature = "sim"))]
+pub mod fault;
+
+pub use self::adc_control::{AdcControl, AdcSampleTs, SampleRate, TsCurrent, TsCurrentMode};
 pub use self::power_status::PowerStatus;
 pub use self::power_control::PowerControl;
-pub use self::charging_status::ChargingStatus;
+pub use self::charging_status::{ChargingStatus, Mode};
+pub use self::otg_vbus_status::OtgVbusStatus;
 pub use self::timer_control::TimerControl;
+pub use self::over_temperature::{OverTemperatureControl, OVERTEMPERATURE_TRIP_POINT};
+pub use self::data_buffer::{BufferRecord, BootCounter, DATA_BUFFER_SIZE};
+pub use self::irq::{Irq, IRQ_BANKS};
+pub use self::boot_reason::BootReason;
+pub use self::register_dump::RegisterDump;
+pub use self::cache::CachedAxp209;
+pub use self::adc_snapshot::{AdcSnapshot, ADC_BLOCK_SIZE};
+pub use self::units::{DeciCelsius, MicroAmps, MicroVolts, MicroWatts, MilliAmpHours, MilliAmps, MilliVolts};
+pub use self::channel::{Channel, Reading, CHANNELS};
+pub use self::error::Error;
+pub use self::coulomb_counter::{CoulombControl, CoulombCounter};
+pub use self::fuel_gauge::{Estimate, FuelGauge, GaugeError, GaugeSample, GaugeState, LIPO_OCV};
+pub use self::charge_control::{ChargeControl, TargetVoltage};
+pub use self::battery_state::BatteryState;
+pub use self::battery_time::BatteryTimes;
+pub use self::retry::{Retry, RetryPolicy};
+pub use self::rail::{Rail, RAILS};
+pub use self::rail_policy::{PolicyViolation, RailPolicy};
+pub use self::boards::{Board, BoardRail};
+pub use self::regulator::Regulator;
+pub use self::sequence::{PowerSequence, SequenceError, Step};
+
+use core::time::Duration;
 
 use byteorder::{ByteOrder, BigEndian};
+use hal::blocking::delay::DelayMs;
 use hal::blocking::i2c::{Read, Write, WriteRead};
 
+use battery_time::CurrentAverage;
+use rail::RAIL_COUNT;
+use regulator::RailUsers;
+
 pub const BATTERY_LEVEL_MISSING: u8 = 0x7f;
 /// The address can't be changed
 const ADDRESS: u8 = 0x34;
@@ -99,35 +186,53 @@ enum Registers {
     /// Power status and control registers
     PowerStatus = 0x00,
     ChargingStatus = 0x01,
+    OtgVbusStatus = 0x02,
+    DataBuffer = 0x04,
     PowerControl = 0x12,
+    ChargeControl = 0x33,
+    IrqEnable = 0x40,
+    IrqStatus = 0x48,
     TimerControl = 0x8a,
+    OverTemperature = 0x8f,
+    Gpio0Control = 0x90,
 
     /// ADC Control
-    AdcControl = 0x82,    
-
-    /// ADC Value registers
-    AcinVoltage = 0x56,
-    AcinCurrent = 0x58,
-    VbusVoltage = 0x5a,
-    VbusCurrent = 0x5c,
-    Temperature = 0x5e,
-    BatteryTemperature = 0x62,
-    Gpio0Voltage = 0x64,
-    Gpio1Voltage = 0x66,
-    //InstantaniousBatteryPower = 0x70, // Three bytes?!
-    BatteryVoltage = 0x78,
-    BatteryChargeCurrent = 0x7a,
-    BatteryDischargeCurrent = 0x7c,
-    SystemIpsout = 0x7e,
-
-    //CoulombBattery = 0xb0,
-    //CoulombBatteryDischarge = 0xb4,
-    //CoulombBatteryEncryption = 0xb8,
+    AdcControl = 0x82,
+    AdcSampleTs = 0x84,
+
+    /// ADC value registers are in `channel::CHANNELS`
+
+    CoulombCounter = 0xb0,
+    CoulombControl = 0xb8,
     BatteryLevel = 0xb9,
 }
 
+/// The function bits of the GPIO0 control register, and the two settings
+/// the GPIO LDO uses. These match what Linux writes.
+const GPIO0_FUNCTION: u8 = 0b0000_0111;
+const GPIO0_LDO: u8 = 0b011;
+const GPIO0_FLOATING: u8 = 0b111;
+
 pub struct Axp209<I2C> {
     device: I2C,
+    /// Refuse to read ADC channels that aren't enabled
+    strict: bool,
+    /// Read registers back after `modify_*()` writes them
+    verify_writes: bool,
+    /// Last known `AdcControl`, so strict mode doesn't need to keep asking
+    adc_enabled: Option<AdcControl>,
+    /// How big the battery is, which the chip has no idea about
+    battery_capacity: Option<MilliAmpHours>,
+    /// Smoothed battery current for the time estimates
+    current_average: CurrentAverage,
+    /// The time estimates as of the last `update_battery_times()`
+    battery_times: BatteryTimes,
+    /// What's allowed to happen to the rails
+    rail_policy: RailPolicy,
+    /// Cleared while `without_rail_policy()` is running
+    enforce_policy: bool,
+    /// Who holds a `Regulator` for each rail
+    rail_users: [RailUsers; RAIL_COUNT],
 }
 
 impl<I2C, E> Axp209<I2C>
@@ -135,227 +240,889 @@ where
     I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
 {
     pub fn new(dev: I2C) -> Self {
+        Self::with_rail_policy(dev, RailPolicy::new())
+    }
+
+    /// Like `new()`, but with rules about which rails can be changed. Any
+    /// write that breaks them returns `Error::RailPolicy` without touching
+    /// the chip. See `rail_policy`.
+    pub fn with_rail_policy(dev: I2C, policy: RailPolicy) -> Self {
         Axp209 {
             device: dev,
+            strict: false,
+            verify_writes: false,
+            adc_enabled: None,
+            battery_capacity: None,
+            current_average: CurrentAverage::default(),
+            battery_times: BatteryTimes::default(),
+            rail_policy: policy,
+            enforce_policy: true,
+            rail_users: [RailUsers::default(); RAIL_COUNT],
         }
     }
 
-    fn write_read_byte(&mut self, send: u8) -> Result<u8, E> {
-        let comm: [u8; 1] = [ send ];
-        let mut buf: [u8; 1] = [0];
-        self.device.write_read(ADDRESS, &comm, &mut buf)?;
+    pub fn rail_policy(&self) -> &RailPolicy {
+        &self.rail_policy
+    }
 
-        Ok(buf[0])
+    /// Do something the rail policy wouldn't allow, like turning the power
+    /// off on purpose. The policy is back in force once `f` returns.
+    pub fn without_rail_policy<F, T>(&mut self, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        self.enforce_policy = false;
+        let result = f(self);
+        self.enforce_policy = true;
+
+        result
     }
 
-    /// Many ADC functions on this chip provide their values as a strange
-    /// 10bit value that requires some funky shifting
-    fn get_adc_12bits(&mut self, register: u8) -> Result<u16, E> {
-        let comm: [u8; 1] = [ register ];
-        let mut recv: [u8; 2] = [ 0, 0 ];
-        let mut value: u16;
+    /// Give the bus back
+    pub fn into_inner(self) -> I2C {
+        self.device
+    }
+
+    /// Tell the driver how big the battery is. Needed for
+    /// `update_battery_times()`.
+    pub fn set_battery_capacity(&mut self, value: MilliAmpHours) {
+        self.battery_capacity = Some(value);
+    }
 
-        self.device.write_read(ADDRESS, &comm, &mut recv)?;
+    /// In strict mode, reading an ADC channel that's switched off in
+    /// `AdcControl` returns `Error::ChannelDisabled` rather than whatever
+    /// stale value the chip has lying around. The enable bits are read once
+    /// and remembered, so if something else changes them behind our back
+    /// call `adc_control()` to catch up.
+    pub fn set_strict(&mut self, value: bool) {
+        self.strict = value;
+    }
 
-        // Weird way to store a number if ye ask me!
-        value = (recv[0] as u16) << 4;
-        value |= recv[1] as u16 & 0x0f;
+    /// Read registers back after every `modify_*()` call, and return
+    /// `Error::VerifyFailed` if they don't hold what was written. Power
+    /// control is always checked no matter what this is set to.
+    pub fn set_verify_writes(&mut self, value: bool) {
+        self.verify_writes = value;
+    }
 
-        Ok(value)
+    fn write_read_byte(&mut self, send: u8) -> Result<u8, Error<E>> {
+        let comm: [u8; 1] = [ send ];
+        let mut buf: [u8; 1] = [0];
+        self.device.write_read(ADDRESS, &comm, &mut buf).map_err(Error::I2c)?;
+
+        Ok(buf[0])
     }
 
-    fn get_8bit_register(&mut self, register: u8) -> Result<u8, E> {
+    fn get_8bit_register(&mut self, register: u8) -> Result<u8, Error<E>> {
         let comm: [u8; 1] = [ register ];
         let mut buf: [u8; 1] = [0];
 
-        self.device.write_read(ADDRESS, &comm, &mut buf)?;
+        self.device.write_read(ADDRESS, &comm, &mut buf).map_err(Error::I2c)?;
 
         Ok(buf[0])
     }
 
-    fn set_8bit_register(&mut self, register: u8, value: u8) -> Result<(), E> {
+    fn set_8bit_register(&mut self, register: u8, value: u8) -> Result<(), Error<E>> {
         let comm: [u8; 2] = [ register, value ];
 
-        self.device.write(ADDRESS, &comm)?;
+        self.forget_register(register, 1);
+
+        self.device.write(ADDRESS, &comm).map_err(Error::I2c)?;
 
         Ok(())
     }
 
-    fn get_16bit_register(&mut self, register: u8) -> Result<u16, E> {
+    fn get_16bit_register(&mut self, register: u8) -> Result<u16, Error<E>> {
         let comm: [u8; 1] = [ register ];
         let mut buf: [u8; 2] = [0, 0];
 
-        self.device.write_read(ADDRESS, &comm, &mut buf)?;
+        self.device.write_read(ADDRESS, &comm, &mut buf).map_err(Error::I2c)?;
 
         Ok(BigEndian::read_u16(&buf))
     }
 
-    fn set_16bit_register(&mut self, register: u8, value: u16) -> Result<(), E> {
+    fn set_16bit_register(&mut self, register: u8, value: u16) -> Result<(), Error<E>> {
         let high = (value >> 8) as u8;
         let low = (value & 0x00ff) as u8;
         let comm: [u8; 3] = [register, high, low];
 
-        self.device.write(ADDRESS, &comm)?;
+        self.forget_register(register, 2);
+
+        self.device.write(ADDRESS, &comm).map_err(Error::I2c)?;
+
+        Ok(())
+    }
+
+    /// Check registers hold what was written to them. Only the bits in
+    /// `mask` are compared, as reserved bits can read back as anything.
+    fn verify_registers(&mut self, register: u8, wrote: &[u8], mask: &[u8]) -> Result<(), Error<E>> {
+        let mut read = [0u8; 8];
+        let len = wrote.len();
+        self.get_registers(register, &mut read[..len])?;
+
+        for i in 0..len {
+            if read[i] & mask[i] != wrote[i] & mask[i] {
+                return Err(Error::VerifyFailed {
+                    register: register + i as u8,
+                    wrote: wrote[i],
+                    read: read[i],
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check writing `value` to a register doesn't break the rail policy.
+    /// Only the power control and voltage registers are ever a problem, and
+    /// the chip only gets asked what's there now if the policy needs to know.
+    fn check_rail_policy(&mut self, address: u8, value: u8) -> Result<(), Error<E>> {
+        if !self.enforce_policy {
+            return Ok(());
+        }
+
+        let policy = self.rail_policy;
+
+        if address == Registers::PowerControl as u8 && policy.guards_outputs() {
+            let old = self.power_control()?;
+            policy.check_outputs(old, PowerControl::new(value)).map_err(Error::RailPolicy)?;
+        }
+
+        for rail in RAILS.iter() {
+            let field = match rail.voltage_field() {
+                Some(x) if x.register == address && policy.guards_voltage(*rail) => x,
+                _ => continue,
+            };
+
+            let old = field.decode(self.get_8bit_register(address)?);
+            policy.check_voltage(*rail, old, field.decode(value)).map_err(Error::RailPolicy)?;
+        }
+
+        Ok(())
+    }
+
+    /// Read-modify-write consecutive registers. `f` gets to change the bits
+    /// in `mask`, everything else is written back exactly as it was read so
+    /// bits this crate doesn't know about are left alone. The rail policy is
+    /// checked before anything is written.
+    fn modify_registers<F>(&mut self, register: u8, mask: &[u8], verify: bool, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut [u8]),
+    {
+        let len = mask.len();
+        let mut old = [0u8; 8];
+        self.get_registers(register, &mut old[..len])?;
+
+        let mut new = old;
+        f(&mut new[..len]);
+
+        for i in 0..len {
+            new[i] = (new[i] & mask[i]) | (old[i] & !mask[i]);
+            self.check_rail_policy(register + i as u8, new[i])?;
+        }
+
+        self.set_registers(register, &new[..len])?;
+
+        if verify {
+            self.verify_registers(register, &new[..len], mask)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drop anything we remember about registers that are about to be written
+    fn forget_register(&mut self, register: u8, count: usize) {
+        let adc = Registers::AdcControl as usize;
+        let first = register as usize;
+
+        if first <= adc + 1 && adc < first + count {
+            self.adc_enabled = None;
+        }
+    }
+
+    /// Read consecutive registers in one go. Only meant for the handful of
+    /// small register blocks on the chip.
+    fn get_registers(&mut self, register: u8, buf: &mut [u8]) -> Result<(), Error<E>> {
+        let comm: [u8; 1] = [ register ];
+
+        self.device.write_read(ADDRESS, &comm, buf).map_err(Error::I2c)?;
+
+        Ok(())
+    }
+
+    /// Write consecutive registers in one go. Only meant for the handful of
+    /// small register blocks on the chip, the biggest of which is the data
+    /// buffer, and every caller passes a fixed size array that fits.
+    fn set_registers(&mut self, register: u8, values: &[u8]) -> Result<(), Error<E>> {
+        let mut comm = [0u8; DATA_BUFFER_SIZE + 1];
+        let len = values.len() + 1;
+
+        comm[0] = register;
+        comm[1..len].copy_from_slice(values);
+
+        self.forget_register(register, values.len());
+
+        self.device.write(ADDRESS, &comm[..len]).map_err(Error::I2c)?;
 
         Ok(())
-    }    
+    }
+
+    pub fn adc_control(&mut self) -> Result<AdcControl, Error<E>> {
+        let value = AdcControl::new(self.get_16bit_register(Registers::AdcControl as u8)?);
+        self.adc_enabled = Some(value);
 
-    pub fn adc_control(&mut self) -> Result<AdcControl, E> {
-        Ok(AdcControl::new(self.get_16bit_register(Registers::AdcControl as u8)?))
+        Ok(value)
     }
 
-    pub fn set_adc_control(&mut self, value: AdcControl) -> Result<(), E> {
-        Ok(self.set_16bit_register(Registers::AdcControl as u8, value.bits())?)
+    pub fn set_adc_control(&mut self, value: AdcControl) -> Result<(), Error<E>> {
+        self.set_16bit_register(Registers::AdcControl as u8, value.bits())?;
+        self.adc_enabled = Some(value);
+
+        Ok(())
+    }
+
+    /// Switch ADC channels on and off without touching the others or the
+    /// reserved bits
+    pub fn modify_adc_control<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut AdcControl),
+    {
+        let mut mask = [0u8; 2];
+        BigEndian::write_u16(&mut mask, AdcControl::all().bits());
+        let verify = self.verify_writes;
+
+        self.modify_registers(Registers::AdcControl as u8, &mask, verify, |x| {
+            let mut value = AdcControl::new(BigEndian::read_u16(x));
+            f(&mut value);
+            BigEndian::write_u16(x, value.bits());
+        })
+    }
+
+    pub fn adc_sample_ts(&mut self) -> Result<AdcSampleTs, Error<E>> {
+        Ok(AdcSampleTs::new(self.get_8bit_register(Registers::AdcSampleTs as u8)?))
+    }
+
+    pub fn set_adc_sample_ts(&mut self, value: AdcSampleTs) -> Result<(), Error<E>> {
+        self.set_8bit_register(Registers::AdcSampleTs as u8, value.bits())
+    }
+
+    pub fn modify_adc_sample_ts<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut AdcSampleTs),
+    {
+        let verify = self.verify_writes;
+
+        // Bit 3 is reserved, the rest are sample rate and TS pin settings
+        self.modify_registers(Registers::AdcSampleTs as u8, &[0b1111_0111], verify, |x| {
+            let mut value = AdcSampleTs::new(x[0]);
+            f(&mut value);
+            x[0] = value.bits();
+        })
+    }
+
+    /// Switch on any of the channels that are off, and if anything changed
+    /// wait one sample period so they have a real value to read.
+    pub fn ensure_channels<D>(&mut self, channels: &[Channel], delay: &mut D) -> Result<(), Error<E>>
+    where
+        D: DelayMs<u16>,
+    {
+        let control = self.adc_control()?;
+        let mut wanted = control;
+
+        for channel in channels {
+            wanted.insert(channel.info().enable);
+        }
+
+        if wanted != control {
+            self.set_adc_control(wanted)?;
+
+            let rate = self.adc_sample_ts()?.sample_rate();
+            delay.delay_ms(rate.period_ms());
+        }
+
+        Ok(())
     }
     
-    pub fn power_status(&mut self) -> Result<PowerStatus, E> {
+    pub fn power_status(&mut self) -> Result<PowerStatus, Error<E>> {
         Ok(PowerStatus::new(self.get_8bit_register(Registers::PowerStatus as u8)?))
     }
 
-    pub fn power_control(&mut self) -> Result<PowerControl, E> {
+    pub fn power_control(&mut self) -> Result<PowerControl, Error<E>> {
         Ok(PowerControl::new(self.get_8bit_register(Registers::PowerControl as u8)?))
     }
 
     /// Enable or disable voltage outputs. This can be dangerous depending on how
     /// the chip has been wired into a circuit. Check the `PowerControl` docs for
-    /// some examples.
-    pub fn set_power_control(&mut self, value: PowerControl) -> Result<(), E> {
-        Ok(self.set_8bit_register(Registers::PowerControl as u8, value.bits())?)
+    /// some examples, and `rail_policy` for a way to make it less so.
+    ///
+    /// The register is read back afterwards, and if it doesn't match you get
+    /// `Error::VerifyFailed` as the rails are in some state you didn't ask for.
+    ///
+    /// This goes around any `Regulator` consumers, so a shared rail can be
+    /// switched off under them (see `regulator`).
+    pub fn set_power_control(&mut self, value: PowerControl) -> Result<(), Error<E>> {
+        self.check_rail_policy(Registers::PowerControl as u8, value.bits())?;
+        self.set_8bit_register(Registers::PowerControl as u8, value.bits())?;
+        self.verify_registers(Registers::PowerControl as u8, &[value.bits()], &[PowerControl::all().bits()])
+    }
+
+    /// Change some of the outputs, leaving the rest and the reserved bits as
+    /// they are. Always verified, like `set_power_control()`.
+    pub fn modify_power_control<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut PowerControl),
+    {
+        let mask = PowerControl::all().bits();
+        let old = self.get_8bit_register(Registers::PowerControl as u8)?;
+
+        let mut value = PowerControl::new(old);
+        f(&mut value);
+
+        self.set_power_control(PowerControl::new((value.bits() & mask) | (old & !mask)))
+    }
+
+    /// A new consumer's handle on a rail, for sharing it with other parts of
+    /// the program (see `regulator`)
+    pub fn regulator(&mut self, rail: Rail) -> Result<Regulator, Error<E>> {
+        match self.rail_users[rail as usize].take() {
+            Some(x) => Ok(Regulator::new(rail, x)),
+            None => Err(Error::NoConsumerSlots(rail)),
+        }
+    }
+
+    /// Switch one rail on or off, leaving the rest alone. Like
+    /// `set_power_control()` this doesn't care about `Regulator` consumers.
+    pub fn set_rail_enabled(&mut self, rail: Rail, value: bool) -> Result<(), Error<E>> {
+        self.modify_power_control(|pc| pc.set(rail.flag(), value))
+    }
+
+    /// Switch on or off everything powering something on a board, like the
+    /// WiFi module (see `boards`)
+    pub fn set_function_enabled(&mut self, board: &Board, function: boards::Function, value: bool) -> Result<(), Error<E>> {
+        let flags = board.flags(function);
+
+        if !flags.is_empty() {
+            self.modify_power_control(|pc| pc.set(flags, value))?;
+        }
+
+        if board.uses_gpio_ldo(function) {
+            self.set_gpio_ldo_enabled(value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether GPIO0 is working as an LDO
+    pub fn gpio_ldo_enabled(&mut self) -> Result<bool, Error<E>> {
+        Ok(self.get_8bit_register(Registers::Gpio0Control as u8)? & GPIO0_FUNCTION == GPIO0_LDO)
+    }
+
+    /// Make GPIO0 an LDO, or leave it floating. Its voltage is set with
+    /// `set_gpio_ldo_voltage()`.
+    pub fn set_gpio_ldo_enabled(&mut self, value: bool) -> Result<(), Error<E>> {
+        let verify = self.verify_writes;
+
+        self.modify_registers(Registers::Gpio0Control as u8, &[GPIO0_FUNCTION], verify, |x| {
+            x[0] = if value { GPIO0_LDO } else { GPIO0_FLOATING };
+        })
+    }
+
+    pub fn gpio_ldo_voltage(&mut self) -> Result<MilliVolts, Error<E>> {
+        Ok(rail::GPIO_LDO_VOLTAGE.decode(self.get_8bit_register(rail::GPIO_LDO_VOLTAGE.register)?))
+    }
+
+    /// Set GPIO0's LDO voltage, rounded down to the nearest 100mV.
+    /// `Error::GpioVoltageOutOfRange` if it can't do that voltage.
+    pub fn set_gpio_ldo_voltage(&mut self, value: MilliVolts) -> Result<(), Error<E>> {
+        let field = rail::GPIO_LDO_VOLTAGE;
+        let code = field.encode(value).ok_or(Error::GpioVoltageOutOfRange(value))?;
+        let verify = self.verify_writes;
+
+        self.modify_registers(field.register, &[field.mask], verify, |x| x[0] = code)
+    }
+
+    /// What a rail's voltage is set to. `Error::NoVoltage` for EXTEN, which
+    /// doesn't have one.
+    pub fn rail_voltage(&mut self, rail: Rail) -> Result<MilliVolts, Error<E>> {
+        let field = rail.voltage_field().ok_or(Error::NoVoltage(rail))?;
+
+        Ok(field.decode(self.get_8bit_register(field.register)?))
+    }
+
+    /// Set a rail's voltage, rounded down to the nearest step (see `rail`).
+    /// `Error::VoltageOutOfRange` if the rail can't do that voltage, and
+    /// `Error::NoVoltage` for EXTEN.
+    pub fn set_rail_voltage(&mut self, rail: Rail, value: MilliVolts) -> Result<(), Error<E>> {
+        let field = rail.voltage_field().ok_or(Error::NoVoltage(rail))?;
+        let code = field.encode(value).ok_or(Error::VoltageOutOfRange(rail, value))?;
+        let verify = self.verify_writes;
+
+        // LDO2 and LDO4 share a register, so only touch our bits
+        let old = self.get_8bit_register(field.register)?;
+        let new = (old & !field.mask) | code;
+
+        self.check_rail_policy(field.register, new)?;
+        self.set_8bit_register(field.register, new)?;
+
+        if verify {
+            self.verify_registers(field.register, &[new], &[field.mask])?;
+        }
+
+        Ok(())
+    }
+
+    /// Change a rail's voltage based on what it's set to now, leaving the
+    /// other rail sharing the register alone. If `f` picks a voltage the rail
+    /// can't do, the register is written back as it was and you get
+    /// `Error::VoltageOutOfRange`.
+    pub fn modify_rail_voltage<F>(&mut self, rail: Rail, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut MilliVolts),
+    {
+        let field = rail.voltage_field().ok_or(Error::NoVoltage(rail))?;
+        let verify = self.verify_writes;
+        let mut refused = None;
+
+        self.modify_registers(field.register, &[field.mask], verify, |x| {
+            let mut value = field.decode(x[0]);
+            f(&mut value);
+
+            match field.encode(value) {
+                Some(code) => x[0] = code,
+                None => refused = Some(value),
+            }
+        })?;
+
+        match refused {
+            Some(x) => Err(Error::VoltageOutOfRange(rail, x)),
+            None => Ok(()),
+        }
     }
 
-    pub fn charging_status(&mut self) -> Result<ChargingStatus, E> {
+    pub fn charging_status(&mut self) -> Result<ChargingStatus, Error<E>> {
         Ok(ChargingStatus::new(self.get_8bit_register(Registers::ChargingStatus as u8)?))
     }
 
-    pub fn timer_control(&mut self) -> Result<TimerControl, E> {
+    /// Read all 12 bytes of the data buffer (see `data_buffer`)
+    pub fn read_buffer(&mut self, buf: &mut [u8; DATA_BUFFER_SIZE]) -> Result<(), Error<E>> {
+        self.get_registers(Registers::DataBuffer as u8, buf)
+    }
+
+    /// Write all 12 bytes of the data buffer (see `data_buffer`)
+    pub fn write_buffer(&mut self, buf: &[u8; DATA_BUFFER_SIZE]) -> Result<(), Error<E>> {
+        self.set_registers(Registers::DataBuffer as u8, buf)
+    }
+
+    /// Read a record from the data buffer. `None` means there's nothing
+    /// valid of that type stored.
+    pub fn load_record<T: BufferRecord>(&mut self) -> Result<Option<T>, Error<E>> {
+        let mut buf = [0u8; DATA_BUFFER_SIZE];
+        self.read_buffer(&mut buf)?;
+
+        Ok(data_buffer::decode_record(&buf))
+    }
+
+    /// Replace whatever is in the data buffer with the record
+    pub fn store_record<T: BufferRecord>(&mut self, record: &T) -> Result<(), Error<E>> {
+        self.write_buffer(&data_buffer::encode_record(record))
+    }
+
+    pub fn otg_vbus_status(&mut self) -> Result<OtgVbusStatus, Error<E>> {
+        Ok(OtgVbusStatus::new(self.get_8bit_register(Registers::OtgVbusStatus as u8)?))
+    }
+
+    pub fn timer_control(&mut self) -> Result<TimerControl, Error<E>> {
         Ok(TimerControl::new(self.get_8bit_register(Registers::TimerControl as u8)?))
     }
 
-    pub fn set_timer_control(&mut self, value: TimerControl) -> Result<(), E> {
+    pub fn set_timer_control(&mut self, value: TimerControl) -> Result<(), Error<E>> {
         Ok(self.set_8bit_register(Registers::TimerControl as u8, value.bits())?)
     }
 
-    /// In milliamps
-    pub fn battery_discharging_current(&mut self) -> Result<u16, E> {
-        let comm: [u8; 1] = [ Registers::BatteryDischargeCurrent as u8 ];
-        let mut recv: [u8; 2] = [ 0, 0 ];
-        let mut value: u16;
+    /// Change the timer. The closure always sees `expired()` as false, since
+    /// writing it back would restart the countdown. Set it to do exactly
+    /// that. Verifying only checks the minutes for the same reason.
+    pub fn modify_timer_control<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut TimerControl),
+    {
+        let minutes = !TimerControl::TIMER_EXPIRED.bits();
+        let verify = self.verify_writes;
+        let mut wrote = 0;
+
+        self.modify_registers(Registers::TimerControl as u8, &[0xff], false, |x| {
+            let mut value = TimerControl::new(x[0] & minutes);
+            f(&mut value);
+            x[0] = value.bits();
+            wrote = x[0];
+        })?;
+
+        if verify {
+            self.verify_registers(Registers::TimerControl as u8, &[wrote], &[minutes])?;
+        }
 
-        self.device.write_read(ADDRESS, &comm, &mut recv)?;
+        Ok(())
+    }
 
-        // Of course one would have 5 least significant bits and
-        // ruin my get_adc_12bits function above!
-        value = (recv[0] as u16) << 5;
-        value |= recv[1] as u16 & 0x1f;
+    pub fn over_temperature_control(&mut self) -> Result<OverTemperatureControl, Error<E>> {
+        Ok(OverTemperatureControl::new(self.get_8bit_register(Registers::OverTemperature as u8)?))
+    }
 
-        Ok(value / 2)
-    }    
+    pub fn set_over_temperature_control(&mut self, value: OverTemperatureControl) -> Result<(), Error<E>> {
+        self.set_8bit_register(Registers::OverTemperature as u8, value.bits())
+    }
 
-    /// In millivolts
-    pub fn battery_voltage(&mut self) -> Result<u16, E> {
-        let mut value = self.get_adc_12bits(Registers::BatteryVoltage as u8)?;
+    pub fn modify_over_temperature_control<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut OverTemperatureControl),
+    {
+        let verify = self.verify_writes;
+
+        self.modify_registers(Registers::OverTemperature as u8, &[OverTemperatureControl::all().bits()], verify, |x| {
+            let mut value = OverTemperatureControl::new(x[0]);
+            f(&mut value);
+            x[0] = value.bits();
+        })
+    }
 
-        // Voltage is in 1.1mV increments, so just add 1/10 the value and
-        // avoid those pesky floating point multiplications. :D
-        value += value / 10;
+    /// Which events are allowed to pull the IRQ pin low
+    pub fn irq_enable(&mut self) -> Result<Irq, Error<E>> {
+        let mut banks = [0u8; IRQ_BANKS];
+        self.get_registers(Registers::IrqEnable as u8, &mut banks)?;
 
-        Ok(value)
+        Ok(Irq::from_banks(&banks))
+    }
+
+    pub fn set_irq_enable(&mut self, value: Irq) -> Result<(), Error<E>> {
+        self.set_registers(Registers::IrqEnable as u8, &value.to_banks())
     }
 
-    /// In milliamps
-    pub fn battery_charging_current(&mut self) -> Result<u16, E> {
-        let value = self.get_adc_12bits(Registers::BatteryChargeCurrent as u8)?;
+    pub fn modify_irq_enable<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut Irq),
+    {
+        let verify = self.verify_writes;
+
+        self.modify_registers(Registers::IrqEnable as u8, &Irq::all().to_banks(), verify, |x| {
+            let mut banks = [0u8; IRQ_BANKS];
+            banks.copy_from_slice(x);
 
-        Ok(value / 2)
+            let mut value = Irq::from_banks(&banks);
+            f(&mut value);
+            x.copy_from_slice(&value.to_banks());
+        })
     }
 
-    /// In millivolts
-    pub fn acin_voltage(&mut self) -> Result<u16, E> {
-        let mut value = self.get_adc_12bits(Registers::AcinVoltage as u8)?;
+    /// Which events have happened since they were last cleared
+    pub fn irq_status(&mut self) -> Result<Irq, Error<E>> {
+        let mut banks = [0u8; IRQ_BANKS];
+        self.get_registers(Registers::IrqStatus as u8, &mut banks)?;
 
-        value += value / 7;
+        Ok(Irq::from_banks(&banks))
+    }
 
-        Ok(value)
+    /// Clear the given events. Anything not passed in is left alone.
+    pub fn clear_irq(&mut self, value: Irq) -> Result<(), Error<E>> {
+        // Writing a one clears the bit, and a zero does nothing
+        self.set_registers(Registers::IrqStatus as u8, &value.to_banks())
     }
 
-    /// In milliamps
-    pub fn acin_current(&mut self) -> Result<u16, E> {
-        let mut value = self.get_adc_12bits(Registers::AcinCurrent as u8)?;
+    /// Why the system came up. This clears the power-on events it used to
+    /// decide, so only the first call after boot will be meaningful. Check
+    /// out the `boot_reason` module for the details.
+    pub fn boot_reason(&mut self) -> Result<BootReason, Error<E>> {
+        let irq = self.irq_status()?;
+        let power = self.power_status()?;
 
-        // Trying to avoid too much rounding as it's multiples of 0.625 milliamps.
-        // For similar odd math with explination, check out vbus_current()
-        value = ((value * 16) / 10) / 16;
+        self.clear_irq(irq & boot_reason::BOOT_EVENTS)?;
 
-        Ok(value)
+        Ok(BootReason::new(irq, power))
     }
 
-    /// In milliamps
-    pub fn vbus_voltage(&mut self) -> Result<u16, E> {
-        let mut value = self.get_adc_12bits(Registers::VbusVoltage as u8)?;
+    /// Read every documented register on the chip (see `register_dump`)
+    pub fn dump_registers(&mut self) -> Result<RegisterDump, Error<E>> {
+        let mut dump = RegisterDump::new([0; 256]);
 
-        value += value / 7;
+        for register in register_dump::REGISTERS {
+            let value = self.get_8bit_register(register.address)?;
+            dump.set(register.address, value);
+        }
 
-        Ok(value)
+        Ok(dump)
     }
 
-    /// In milliamps
-    pub fn vbus_current(&mut self) -> Result<u16, E> {
-        let mut value = self.get_adc_12bits(Registers::VbusCurrent as u8)?;
+    /// Write back every writable register from a snapshot, in an order that
+    /// won't switch outputs on before their voltages are set. Status, ADC and
+    /// IRQ status registers are left alone. The outputs are read back like
+    /// `set_power_control()` does, and `Error::VerifyFailed` means they
+    /// didn't all end up as the dump has them.
+    pub fn restore_registers(&mut self, dump: &RegisterDump) -> Result<(), Error<E>> {
+        // Check everything first, so a dump the rail policy doesn't like
+        // doesn't get halfway restored
+        for address in register_dump::RESTORE_ORDER {
+            self.check_rail_policy(*address, dump.restore_value(*address))?;
+        }
 
-        // Trying to avoid too much rounding as it's multiples of 0.375 milliamps
-        // The max this register will return is 4096, so we have enough headroom
-        // to multiply by 16, and 0.375*16 (probably by design) comes out as 6.
-        value = ((value * 16) / 6) / 16;
+        for address in register_dump::RESTORE_ORDER {
+            let value = dump.restore_value(*address);
+            self.set_8bit_register(*address, value)?;
 
-        Ok(value)
+            if *address == Registers::PowerControl as u8 {
+                self.verify_registers(*address, &[value], &[PowerControl::all().bits()])?;
+            }
+        }
+
+        Ok(())
     }
 
-    /// In celcius
-    pub fn temperature(&mut self) -> Result<i16, E> {
-        // Check out page 25 of the datasheet for the weird math
+    /// The raw code from an ADC channel, before any conversion. Feed it to
+    /// the matching function in `convert` to get full precision.
+    pub fn adc_raw(&mut self, channel: Channel) -> Result<u32, Error<E>> {
+        let info = channel.info();
+        let mut recv = [0u8; 4];
+
+        if self.strict {
+            let enabled = match self.adc_enabled {
+                Some(x) => x,
+                None => self.adc_control()?,
+            };
+
+            if !enabled.contains(info.enable) {
+                return Err(Error::ChannelDisabled(channel));
+            }
+        }
 
-        let value = self.get_adc_12bits(Registers::Temperature as u8)?;
+        self.get_registers(info.register, &mut recv[..info.register_count()])?;
 
-        let mut value = value as i16 / 10;
-        value -= 145;
+        Ok(info.decode(&recv))
+    }
 
-        Ok(value)
+    /// Read any ADC channel at full precision
+    pub fn read_channel(&mut self, channel: Channel) -> Result<Reading, Error<E>> {
+        let raw = self.adc_raw(channel)?;
+
+        Ok(channel.info().reading(raw))
+    }
+
+    /// Read a channel in the base unit of its `Quantity`
+    fn channel_value(&mut self, channel: Channel) -> Result<i32, Error<E>> {
+        let raw = self.adc_raw(channel)?;
+
+        Ok(channel.info().value(raw))
+    }
+
+    /// Read every enabled ADC channel in one go. Besides being a lot less
+    /// chatty on the bus, all the values come from the same moment in time.
+    pub fn adc_snapshot(&mut self) -> Result<AdcSnapshot, Error<E>> {
+        let control = self.adc_control()?;
+        let mut block = [0u8; ADC_BLOCK_SIZE];
+
+        self.get_registers(CHANNELS[0].register, &mut block)?;
+
+        Ok(AdcSnapshot::new(&block, control))
+    }
+
+    pub fn battery_discharging_current(&mut self) -> Result<MilliAmps, Error<E>> {
+        Ok(MicroAmps(self.channel_value(Channel::BatteryDischargeCurrent)? as u32).into())
+    }
+
+    pub fn battery_voltage(&mut self) -> Result<MilliVolts, Error<E>> {
+        Ok(MicroVolts(self.channel_value(Channel::BatteryVoltage)? as u32).into())
+    }
+
+    pub fn battery_charging_current(&mut self) -> Result<MilliAmps, Error<E>> {
+        Ok(MicroAmps(self.channel_value(Channel::BatteryChargeCurrent)? as u32).into())
+    }
+
+    pub fn acin_voltage(&mut self) -> Result<MilliVolts, Error<E>> {
+        Ok(MicroVolts(self.channel_value(Channel::AcinVoltage)? as u32).into())
+    }
+
+    pub fn acin_current(&mut self) -> Result<MilliAmps, Error<E>> {
+        Ok(MicroAmps(self.channel_value(Channel::AcinCurrent)? as u32).into())
+    }
+
+    pub fn vbus_voltage(&mut self) -> Result<MilliVolts, Error<E>> {
+        Ok(MicroVolts(self.channel_value(Channel::VbusVoltage)? as u32).into())
+    }
+
+    pub fn vbus_current(&mut self) -> Result<MilliAmps, Error<E>> {
+        Ok(MicroAmps(self.channel_value(Channel::VbusCurrent)? as u32).into())
+    }
+
+    /// The chip's internal temperature
+    pub fn temperature(&mut self) -> Result<DeciCelsius, Error<E>> {
+        Ok(DeciCelsius(self.channel_value(Channel::Temperature)? as i16))
+    }
+
+    /// How far the chip is from its over-temperature shutdown point.
+    /// Negative values mean it's already past it, which (if shutdown is
+    /// enabled) you're unlikely to ever see.
+    pub fn thermal_headroom(&mut self) -> Result<DeciCelsius, Error<E>> {
+        let value = self.temperature()?;
+
+        Ok(OVERTEMPERATURE_TRIP_POINT - value)
+    }
+
+    /// Battery temperature sensor
+    pub fn ts_voltage(&mut self) -> Result<MilliVolts, Error<E>> {
+        Ok(MicroVolts(self.channel_value(Channel::TsVoltage)? as u32).into())
+    }
+
+    /// See `convert::ipsout_voltage()` for a word of warning
+    pub fn ipsout_voltage(&mut self) -> Result<MilliVolts, Error<E>> {
+        Ok(MicroVolts(self.channel_value(Channel::IpsoutVoltage)? as u32).into())
+    }
+
+    /// Unconfirmed
+    pub fn gpio0_voltage(&mut self) -> Result<MilliVolts, Error<E>> {
+        Ok(MicroVolts(self.channel_value(Channel::Gpio0Voltage)? as u32).into())
+    }
+
+    /// Unconfirmed
+    pub fn gpio1_voltage(&mut self) -> Result<MilliVolts, Error<E>> {
+        Ok(MicroVolts(self.channel_value(Channel::Gpio1Voltage)? as u32).into())
+    }
+
+    /// How much power is flowing in or out of the battery right now
+    pub fn battery_power(&mut self) -> Result<MicroWatts, Error<E>> {
+        Ok(MicroWatts(self.channel_value(Channel::BatteryPower)? as u32))
+    }
+
+    pub fn charge_control(&mut self) -> Result<ChargeControl, Error<E>> {
+        Ok(ChargeControl::new(self.get_8bit_register(Registers::ChargeControl as u8)?))
+    }
+
+    pub fn set_charge_control(&mut self, value: ChargeControl) -> Result<(), Error<E>> {
+        self.set_8bit_register(Registers::ChargeControl as u8, value.bits())
+    }
+
+    pub fn modify_charge_control<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut ChargeControl),
+    {
+        let verify = self.verify_writes;
+
+        // Bit 7 is ENABLE, 5 and 6 the target voltage, 4 END_CURRENT_15 and
+        // 0 to 3 the current, so every bit is spoken for
+        self.modify_registers(Registers::ChargeControl as u8, &[0xff], verify, |x| {
+            let mut value = ChargeControl::new(x[0]);
+            f(&mut value);
+            x[0] = value.bits();
+        })
+    }
+
+    /// Read the battery current into the smoothed average, and work out how
+    /// long until the battery runs out, and until it's full, at the rate it's
+    /// going. At most one of them is ever `Some`, and neither is if the
+    /// battery capacity isn't known. This is the only thing that feeds the
+    /// average, so call it once per tick of your UI. See `battery_time` for
+    /// the fine print.
+    pub fn update_battery_times(&mut self) -> Result<BatteryTimes, Error<E>> {
+        let charging = self.battery_charging_current()?;
+        let discharging = self.battery_discharging_current()?;
+        let capacity = self.battery_capacity;
+        let level = self.battery_level()?;
+        let limit = self.charge_control()?.current();
+
+        let average = self.current_average.update(charging, discharging);
+        self.battery_times = match capacity {
+            Some(x) if average > 0 => BatteryTimes {
+                to_empty: None,
+                to_full: battery_time::time_to_full(level, x, average, limit),
+            },
+            Some(x) => BatteryTimes {
+                to_empty: battery_time::time_to_empty(level, x, average),
+                to_full: None,
+            },
+            None => BatteryTimes::default(),
+        };
+
+        Ok(self.battery_times)
+    }
+
+    /// Both estimates from the last `update_battery_times()`, without
+    /// touching the bus
+    pub fn battery_times(&self) -> BatteryTimes {
+        self.battery_times
     }
 
-    /// In millivolts. Battery temperature sensor
-    pub fn ts_voltage(&mut self) -> Result<u16, E> {
-        let value = self.get_adc_12bits(Registers::BatteryTemperature as u8)?;
+    /// The time to empty from the last `update_battery_times()`
+    pub fn time_to_empty(&self) -> Option<Duration> {
+        self.battery_times.to_empty
+    }
 
-        // Increments of 0.8
-        Ok((value * 8) / 10)
+    /// The time to full from the last `update_battery_times()`
+    pub fn time_to_full(&self) -> Option<Duration> {
+        self.battery_times.to_full
     }
 
-    /// In millivolts. I'm assuming power division is 1.4 as defined in APS, but
-    /// as there is nothing in the datasheet specifically for Ipsout's settings
-    /// and there is no register defined for ipsout.
-    pub fn ipsout_voltage(&mut self) -> Result<u16, E> {
-        let value = self.get_adc_12bits(Registers::SystemIpsout as u8)?;
+    pub fn coulomb_control(&mut self) -> Result<CoulombControl, Error<E>> {
+        Ok(CoulombControl::new(self.get_8bit_register(Registers::CoulombControl as u8)?))
+    }
 
-        // Increments of 1.4
-        Ok((value * 14) / 10)
+    pub fn set_coulomb_control(&mut self, value: CoulombControl) -> Result<(), Error<E>> {
+        self.set_8bit_register(Registers::CoulombControl as u8, value.bits())
     }
 
-    /// In millivolts. Unconfirmed
-    pub fn gpio0_voltage(&mut self) -> Result<u16, E> {
-        let value = self.get_adc_12bits(Registers::Gpio0Voltage as u8)?;
+    /// Change the coulomb counter settings. `CLEAR` doesn't stay set on the
+    /// chip, so it's left out when verifying.
+    pub fn modify_coulomb_control<F>(&mut self, f: F) -> Result<(), Error<E>>
+    where
+        F: FnOnce(&mut CoulombControl),
+    {
+        let mask = CoulombControl::all().bits();
+        let verify = self.verify_writes;
+        let mut wrote = 0;
+
+        self.modify_registers(Registers::CoulombControl as u8, &[mask], false, |x| {
+            let mut value = CoulombControl::new(x[0]);
+            f(&mut value);
+            x[0] = value.bits();
+            wrote = x[0];
+        })?;
+
+        if verify {
+            let mask = mask & !CoulombControl::CLEAR.bits();
+            self.verify_registers(Registers::CoulombControl as u8, &[wrote], &[mask])?;
+        }
 
-        Ok(value / 2)
+        Ok(())
     }
 
-    /// In millivolts. Unconfirmed
-    pub fn gpio1_voltage(&mut self) -> Result<u16, E> {
-        let value = self.get_adc_12bits(Registers::Gpio1Voltage as u8)?;
+    /// The raw coulomb counter totals (see `coulomb_counter`)
+    pub fn coulomb_counter(&mut self) -> Result<CoulombCounter, Error<E>> {
+        let mut recv = [0u8; 8];
+        self.get_registers(Registers::CoulombCounter as u8, &mut recv)?;
+
+        Ok(CoulombCounter::new(&recv))
+    }
 
-        Ok(value / 2)
+    /// Take a fresh set of readings and feed them to the fuel gauge (see
+    /// `fuel_gauge`)
+    pub fn update_fuel_gauge(&mut self, gauge: &mut FuelGauge) -> Result<Estimate, Error<E>> {
+        let rate = self.adc_sample_ts()?.sample_rate();
+        let counter = self.coulomb_counter()?;
+        let power = self.power_status()?;
+        let charging = self.charging_status()?;
+        let voltage = self.battery_voltage()?;
+
+        let powered = power.intersects(PowerStatus::ACIN_USABLE | PowerStatus::VBUS_USABLE);
+        let sample = GaugeSample {
+            coulomb: counter.net_charge(rate),
+            voltage,
+            charging_current: self.battery_charging_current()?,
+            discharging_current: self.battery_discharging_current()?,
+            full: powered && charging.contains(ChargingStatus::BATTERY_PRESENT)
+                && !charging.contains(ChargingStatus::CHARGING)
+                && voltage >= fuel_gauge::FULL_VOLTAGE,
+        };
+
+        Ok(gauge.update(&sample))
     }
 
     // In percentage.
-    pub fn battery_level(&mut self) -> Result<u8, E> {
+    pub fn battery_level(&mut self) -> Result<u8, Error<E>> {
         // The MSB for the voltage is a control bit that enables or
         // disables sampling
         match self.write_read_byte(Registers::BatteryLevel as u8) {
@@ -364,23 +1131,38 @@ where
         }
     }
 
-    pub fn battery_present(&mut self) -> Result<bool, E> {
+    pub fn battery_present(&mut self) -> Result<bool, Error<E>> {
         let level = self.battery_level()?;
 
-        Ok(level == BATTERY_LEVEL_MISSING)
+        Ok(level != BATTERY_LEVEL_MISSING)
+    }
+
+    /// What the battery is up to, without having to combine the status
+    /// registers by hand. See `BatteryState`.
+    pub fn battery_state(&mut self) -> Result<BatteryState, Error<E>> {
+        let power = self.power_status()?;
+        let charging = self.charging_status()?;
+        let charge = self.charge_control()?;
+        let level = self.battery_level()?;
+
+        Ok(BatteryState::new(power, charging, charge, level))
     }
 }
 
 #[cfg(test)]
 mod tests {
     extern crate linux_embedded_hal as linux_hal;
+    extern crate std;
 
     use super::*;
+    use self::std::format;
 
     use hal::digital::OutputPin;
     use self::linux_hal::{Pin, I2cdev};
 
+    // Needs GPIO 135 on a real C.H.I.P., run with `cargo test -- --ignored`
     #[test]
+    #[ignore]
     fn permissions() {
         let mut gpio = Pin::new(135);
         let state = gpio.is_low();
@@ -392,7 +1174,9 @@ mod tests {
         }
     }
 
+    // Needs the AXP209 on /dev/i2c-0, run with `cargo test -- --ignored`
     #[test]
+    #[ignore]
     fn battery_level() {
         let i2c = I2cdev::new("/dev/i2c-0").unwrap();
         let mut pmic = Axp209::new(i2c);
@@ -402,6 +1186,930 @@ mod tests {
         // 0x7F if the battery is missing
     }
 
+    #[test]
+    fn over_temperature_decoding() {
+        // The power-on default: shutdown on, N_OE shutdown on
+        let control = OverTemperatureControl::new(0x44);
+
+        assert!(control.contains(OverTemperatureControl::SHUTDOWN));
+        assert!(control.contains(OverTemperatureControl::NOE_SHUTDOWN));
+        assert!(!control.contains(OverTemperatureControl::IRQ_WAKEUP));
+        assert!(!control.contains(OverTemperatureControl::VBUS_ACIN_SHORTED));
+        assert_eq!(OverTemperatureControl::new(0xff).bits(), 0xff);
+
+        // 0.1°C per step from -144.7°C, so 2000 is 55.3°C and 2897 is the
+        // trip point
+        let mut pmic = Axp209::new(sim::Sim::new());
+
+        pmic.device.set_adc(Channel::Temperature, 2000);
+        assert_eq!(pmic.thermal_headroom().unwrap(), DeciCelsius(897));
+        pmic.device.set_adc(Channel::Temperature, 2897);
+        assert_eq!(pmic.thermal_headroom().unwrap(), DeciCelsius(0));
+        pmic.device.set_adc(Channel::Temperature, 2900);
+        assert_eq!(pmic.thermal_headroom().unwrap(), DeciCelsius(-3));
+    }
+
+    #[test]
+    fn buffer_record_round_trip() {
+        let counter = BootCounter { count: 0xdead_beef };
+        let mut buf = data_buffer::encode_record(&counter);
+
+        assert_eq!(data_buffer::decode_record::<BootCounter>(&buf), Some(counter));
+
+        // A flipped bit or an empty buffer shouldn't be mistaken for data
+        buf[3] ^= 0x10;
+        assert_eq!(data_buffer::decode_record::<BootCounter>(&buf), None);
+        assert_eq!(data_buffer::decode_record::<BootCounter>(&[0; DATA_BUFFER_SIZE]), None);
+    }
+
+    #[test]
+    fn boot_reason_decoding() {
+        let from_power = PowerStatus::START_ON_POWER | PowerStatus::VBUS_PRESENT;
+
+        assert_eq!(BootReason::new(Irq::PEK_SHORT_PRESS, PowerStatus::VBUS_PRESENT),
+                   BootReason::PowerKey);
+        // Started by the plug, so the key was pressed later on
+        assert_eq!(BootReason::new(Irq::PEK_SHORT_PRESS | Irq::VBUS_PLUGGED, from_power),
+                   BootReason::VbusInserted);
+        assert_eq!(BootReason::new(Irq::VBUS_PLUGGED, from_power), BootReason::VbusInserted);
+        assert_eq!(BootReason::new(Irq::TIMER, PowerStatus::empty()), BootReason::TimerExpired);
+        assert_eq!(BootReason::new(Irq::empty(), from_power), BootReason::WarmReboot);
+
+        // Plugged in after a power key boot whose events were already cleared
+        assert_eq!(BootReason::new(Irq::VBUS_PLUGGED, PowerStatus::VBUS_PRESENT),
+                   BootReason::WarmReboot);
+    }
+
+    #[test]
+    fn register_dump_round_trip() {
+        let mut pmic = Axp209::new(sim::Sim::new());
+
+        pmic.write_buffer(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]).unwrap();
+        pmic.set_power_control(PowerControl::DCDC2 | PowerControl::DCDC3).unwrap();
+        pmic.set_8bit_register(0x23, 0x16).unwrap();
+        let dump = pmic.dump_registers().unwrap();
+        assert_eq!(dump.get(0x12), (PowerControl::DCDC2 | PowerControl::DCDC3).bits());
+
+        // Mess everything up, then put it back
+        pmic.write_buffer(&[0; DATA_BUFFER_SIZE]).unwrap();
+        pmic.set_power_control(PowerControl::all()).unwrap();
+        pmic.set_8bit_register(0x23, 0x00).unwrap();
+        pmic.restore_registers(&dump).unwrap();
+
+        assert_eq!(&pmic.dump_registers().unwrap().as_bytes()[..], &dump.as_bytes()[..]);
+        // Outputs go last, once their voltages are in
+        assert_eq!(pmic.device.last_written(), Some(0x12));
+    }
+
+    #[test]
+    fn register_restore_verifies_outputs() {
+        use fault::{Fault, FaultKind, Faulty};
+
+        let mut pmic = Axp209::new(Faulty::new(sim::Sim::new()));
+        pmic.set_power_control(PowerControl::DCDC2 | PowerControl::DCDC3).unwrap();
+        let dump = pmic.dump_registers().unwrap();
+        pmic.set_power_control(PowerControl::DCDC2).unwrap();
+
+        // The outputs didn't take, so they still read as before
+        pmic.device.inject(Fault::new(FaultKind::Stuck(PowerControl::DCDC2.bits())).on_register(0x12).times(1));
+        assert_eq!(pmic.restore_registers(&dump), Err(Error::VerifyFailed {
+            register: 0x12,
+            wrote: (PowerControl::DCDC2 | PowerControl::DCDC3).bits(),
+            read: PowerControl::DCDC2.bits(),
+        }));
+    }
+
+    #[test]
+    fn register_restore_skips_one_shots() {
+        let mut pmic = Axp209::new(sim::Sim::new());
+        let mut dump = pmic.dump_registers().unwrap();
+
+        // A dump with every one-shot bit set, like one taken at just the
+        // wrong moment
+        dump.set(0x32, 0x80 | 0x46);
+        dump.set(0x8a, 0x80 | 5);
+        dump.set(0xb8, 0x80 | 0x20);
+
+        pmic.device.expire_timer();
+        pmic.device.set_register(0xb3, 42);
+        pmic.restore_registers(&dump).unwrap();
+
+        // Not shut down, expired flag not cleared, counters not cleared
+        assert!(!pmic.device.is_shut_down());
+        assert_eq!(pmic.device.register(0x32), 0x46);
+        assert_eq!(pmic.device.register(0x8a), 0x80 | 5);
+        assert_eq!(pmic.device.register(0xb8), 0x80);
+        assert_eq!(pmic.device.register(0xb3), 42);
+    }
+
+    #[test]
+    fn register_cache() {
+        use mock::{Mock, Transaction};
+
+        // Registers with one-shot bits never get a copy kept
+        assert!(cache::is_cacheable(0x12));
+        assert!(cache::is_cacheable(0x33));
+        assert!(!cache::is_cacheable(0x00));
+        assert!(!cache::is_cacheable(0x32));
+        assert!(!cache::is_cacheable(0x8a));
+        assert!(!cache::is_cacheable(0xb8));
+
+        let expected = [
+            Transaction::write_read(0x34, &[0x12], &[0x5f]),
+            Transaction::write_read(0x34, &[0x23], &[0x16]),
+            // Voltages first, then outputs, and only what changed
+            Transaction::write(0x34, &[0x23, 0x18]),
+            Transaction::write(0x34, &[0x12, 0x1f]),
+            Transaction::write_read(0x34, &[0x12], &[0x1f]),
+        ];
+        let mut pmic = CachedAxp209::new(Axp209::new(Mock::new(&expected)));
+
+        // Only the first read of each goes over the bus
+        let outputs = pmic.power_control().unwrap();
+        assert_eq!(pmic.power_control().unwrap(), outputs);
+        pmic.set_power_control(outputs - PowerControl::LDO3);
+        pmic.modify_register(0x23, |x| x + 2).unwrap();
+        pmic.modify_register(0x23, |x| x).unwrap();
+        assert!(pmic.is_dirty());
+
+        pmic.flush().unwrap();
+        assert!(!pmic.is_dirty());
+        // Nothing left to write
+        pmic.flush().unwrap();
+        pmic.into_inner().into_inner().done();
+
+        let mut pmic = CachedAxp209::new(Axp209::new(sim::Sim::new()));
+        assert_eq!(pmic.write_register(0x32, 0x80), Err(Error::NotCacheable(0x32)));
+        assert_eq!(pmic.modify_register(0xb8, |x| x | 0x20), Err(Error::NotCacheable(0xb8)));
+        assert!(!pmic.is_dirty());
+        assert!(!pmic.into_inner().into_inner().is_shut_down());
+    }
+
+    #[test]
+    fn adc_snapshot_decoding() {
+        let mut block = [0u8; ADC_BLOCK_SIZE];
+
+        // Battery voltage of 0xabc (3.0228V), discharge current 0x1234 (13 bits)
+        block[0x78 - 0x56] = 0xab;
+        block[0x79 - 0x56] = 0x0c;
+        block[0x7c - 0x56] = 0x91;
+        block[0x7d - 0x56] = 0x14;
+
+        let snapshot = AdcSnapshot::new(&block, AdcControl::BATTERY_VOLTAGE | AdcControl::BATTERY_CURRENT);
+
+        assert_eq!(snapshot.battery_voltage, Some(MilliVolts(3023)));
+        assert_eq!(snapshot.battery_discharging_current, Some(MilliAmps(2330)));
+        assert_eq!(snapshot.acin_voltage, None);
+    }
+
+    #[test]
+    fn units_display() {
+        assert_eq!(format!("{}", MilliVolts(4184)), "4184mV");
+        assert_eq!(format!("{}", DeciCelsius(473)), "47.3°C");
+        assert_eq!(format!("{}", DeciCelsius(-5)), "-0.5°C");
+        assert_eq!(MilliVolts(4000) * MilliAmps(500), MicroWatts(2_000_000));
+    }
+
+    #[test]
+    // Checked against the ADC table in the datasheet, using the full scale
+    // of each channel
+    fn adc_conversion_steps() {
+        let max12 = 0xfff;
+        let max13 = 0x1fff;
+
+        assert_eq!(convert::battery_voltage(max12), MicroVolts(4_504_500));
+        assert_eq!(convert::battery_current(max13), MicroAmps(4_095_500));
+        assert_eq!(convert::input_voltage(max12), MicroVolts(6_961_500));
+        assert_eq!(convert::acin_current(max12), MicroAmps(2_559_375));
+        assert_eq!(convert::vbus_current(max12), MicroAmps(1_535_625));
+        assert_eq!(convert::ts_voltage(max12), MicroVolts(3_276_000));
+        assert_eq!(convert::ipsout_voltage(max12), MicroVolts(5_733_000));
+        assert_eq!(convert::gpio_voltage(max12), MicroVolts(2_047_500));
+        assert_eq!(convert::temperature(0), DeciCelsius(-1447));
+        assert_eq!(convert::temperature(max12), DeciCelsius(2648));
+
+        // Rounding down to the getters' units
+        assert_eq!(MilliAmps::from(convert::acin_current(1)), MilliAmps(1));
+        assert_eq!(MilliVolts::from(convert::battery_voltage(3)), MilliVolts(3));
+        assert_eq!(MilliVolts::from(convert::battery_voltage(5)), MilliVolts(6));
+    }
+
+    #[test]
+    fn channel_decoding() {
+        let bytes = [0xab, 0xfc, 0x12];
+
+        assert_eq!(Channel::BatteryVoltage.info().decode(&bytes), 0xabc);
+        assert_eq!(Channel::BatteryDischargeCurrent.info().decode(&bytes), 0xab << 5 | 0x1c);
+        assert_eq!(Channel::BatteryPower.info().decode(&bytes), 0xabfc12);
+        assert_eq!(Channel::Temperature.info().reading(1447), Reading::Temperature(DeciCelsius(0)));
+    }
+
+    #[test]
+    fn strict_channels() {
+        struct Waited {
+            calls: u32,
+            ms: u16,
+        }
+
+        impl DelayMs<u16> for Waited {
+            fn delay_ms(&mut self, ms: u16) {
+                self.calls += 1;
+                self.ms += ms;
+            }
+        }
+
+        let mut pmic = Axp209::new(sim::Sim::new());
+        pmic.device.set_adc(Channel::Gpio0Voltage, 1000);
+
+        // Off by default, but only strict mode minds
+        assert_eq!(pmic.gpio0_voltage().unwrap(), MilliVolts(500));
+        pmic.set_strict(true);
+        assert_eq!(pmic.gpio0_voltage(), Err(Error::ChannelDisabled(Channel::Gpio0Voltage)));
+        assert_eq!(pmic.read_channel(Channel::Gpio0Voltage), Err(Error::ChannelDisabled(Channel::Gpio0Voltage)));
+        assert_eq!(pmic.battery_voltage().unwrap(), MilliVolts(0));
+
+        // Switching them on waits one sample period, 100Hz by default
+        let mut delay = Waited { calls: 0, ms: 0 };
+        pmic.ensure_channels(&[Channel::Gpio0Voltage, Channel::Gpio1Voltage], &mut delay).unwrap();
+        assert_eq!((delay.calls, delay.ms), (1, 10));
+        assert!(pmic.adc_control().unwrap().contains(AdcControl::GPIO0 | AdcControl::GPIO1));
+        assert_eq!(pmic.gpio0_voltage().unwrap(), MilliVolts(500));
+
+        // Nothing to change, nothing to wait for
+        pmic.ensure_channels(&[Channel::Gpio0Voltage, Channel::BatteryVoltage], &mut delay).unwrap();
+        assert_eq!((delay.calls, delay.ms), (1, 10));
+    }
+
+    #[test]
+    fn adc_control_display() {
+        let control = AdcControl::from_channels(&[Channel::BatteryVoltage, Channel::BatteryChargeCurrent,
+                                                  Channel::AcinVoltage, Channel::AcinCurrent,
+                                                  Channel::IpsoutVoltage, Channel::TsVoltage,
+                                                  Channel::Temperature]);
+
+        // Straight out of the README
+        assert_eq!(format!("{}", control), "BATTERY_VOLTAGE | BATTERY_CURRENT | ACIN_VOLTAGE | \
+                                           ACIN_CURRENT | APS_VOLTAGE | TS | TEMPERATURE");
+        assert!(control.is_enabled(Channel::BatteryDischargeCurrent));
+        assert!(control.is_enabled(Channel::BatteryPower));
+        assert!(!control.vbus_current());
+    }
+
+    #[test]
+    fn fuel_gauge_tracking() {
+        let mut gauge = FuelGauge::new(MilliAmpHours(1000), &LIPO_OCV).unwrap();
+        let mut sample = GaugeSample {
+            coulomb: 0,
+            voltage: MilliVolts(4180),
+            charging_current: MilliAmps(0),
+            discharging_current: MilliAmps(0),
+            full: true,
+        };
+
+        let estimate = gauge.update(&sample);
+        assert_eq!((estimate.level, estimate.confidence), (100, 99));
+
+        // Use up 600mAh under load, then rest at what should be 30%
+        sample.full = false;
+        sample.coulomb = -600_000;
+        sample.voltage = MilliVolts(3600);
+        sample.discharging_current = MilliAmps(500);
+        assert_eq!(gauge.update(&sample).level, 40);
+
+        sample.voltage = MilliVolts(3730);
+        sample.discharging_current = MilliAmps(0);
+        for _ in 0..10 {
+            gauge.update(&sample);
+        }
+
+        // 600mAh took it from 100% to 30%, so the battery is really ~857mAh.
+        // Both the capacity and level only move part way there at once.
+        let estimate = gauge.estimate();
+        assert!(estimate.capacity < MilliAmpHours(1000) && estimate.capacity > MilliAmpHours(900));
+        assert!(estimate.level >= 30 && estimate.level < 40);
+
+        // And it survives being saved
+        let state = gauge.state();
+        let buf = data_buffer::encode_record(&state);
+        assert_eq!(data_buffer::decode_record::<GaugeState>(&buf), Some(state));
+    }
+
+    #[test]
+    fn fuel_gauge_resume() {
+        use fuel_gauge::{ocv_level, OcvPoint};
+
+        let backwards = [
+            OcvPoint { voltage: MilliVolts(4200), level: 100 },
+            OcvPoint { voltage: MilliVolts(3000), level: 0 },
+        ];
+
+        assert_eq!(FuelGauge::new(MilliAmpHours(0), &LIPO_OCV).err(), Some(GaugeError::NoCapacity));
+        assert_eq!(FuelGauge::new(MilliAmpHours(1000), &[]).err(), Some(GaugeError::BadCurve));
+        assert_eq!(FuelGauge::new(MilliAmpHours(1000), &backwards).err(), Some(GaugeError::BadCurve));
+        assert_eq!(ocv_level(&[], MilliVolts(3700)), 0);
+        assert_eq!(ocv_level(&backwards, MilliVolts(3700)), 100);
+
+        // What an empty data buffer decodes to
+        let zeroed = GaugeState {
+            remaining: MilliAmpHours(0),
+            capacity: MilliAmpHours(0),
+            design_capacity: MilliAmpHours(0),
+            confidence: 0,
+            coulomb: Some(0),
+        };
+        assert_eq!(FuelGauge::resume(zeroed, &LIPO_OCV).err(), Some(GaugeError::NoCapacity));
+
+        // What was learned comes back, measured against the original design
+        let learned = GaugeState {
+            remaining: MilliAmpHours(450),
+            capacity: MilliAmpHours(900),
+            design_capacity: MilliAmpHours(1000),
+            confidence: 80,
+            coulomb: Some(-12_000),
+        };
+        let gauge = FuelGauge::resume(learned, &LIPO_OCV).unwrap();
+        assert_eq!(gauge.state(), learned);
+        assert_eq!(gauge.estimate().level, 50);
+
+        // Something learned way off isn't believed
+        let strayed = GaugeState { capacity: MilliAmpHours(400), ..learned };
+        let state = FuelGauge::resume(strayed, &LIPO_OCV).unwrap().state();
+        assert_eq!((state.capacity, state.design_capacity), (MilliAmpHours(1000), MilliAmpHours(1000)));
+
+        // Saved before the first update, so there's no reading to count from
+        let unsampled = FuelGauge::new(MilliAmpHours(1000), &LIPO_OCV).unwrap().state();
+        assert_eq!(unsampled.coulomb, None);
+        let buf = data_buffer::encode_record(&unsampled);
+        assert_eq!(data_buffer::decode_record::<GaugeState>(&buf), Some(unsampled));
+
+        let mut gauge = FuelGauge::resume(unsampled, &LIPO_OCV).unwrap();
+        let before = gauge.estimate();
+        let mut sample = GaugeSample {
+            coulomb: 700_000,
+            voltage: MilliVolts(3600),
+            charging_current: MilliAmps(0),
+            discharging_current: MilliAmps(300),
+            full: false,
+        };
+        assert_eq!(gauge.update(&sample).remaining, before.remaining);
+
+        // A counter cleared while the board was off isn't counted either
+        let mut gauge = FuelGauge::resume(learned, &LIPO_OCV).unwrap();
+        sample.coulomb = 2_000_000;
+        assert_eq!(gauge.update(&sample).remaining, MilliAmpHours(450));
+        sample.coulomb = 1_900_000;
+        assert_eq!(gauge.update(&sample).remaining, MilliAmpHours(350));
+    }
+
+    #[test]
+    fn sim_registers() {
+        let mut pmic = Axp209::new(sim::Sim::new());
+
+        // Read-only registers don't budge
+        pmic.set_8bit_register(Registers::PowerStatus as u8, 0xff).unwrap();
+        assert_eq!(pmic.power_status().unwrap(), PowerStatus::empty());
+
+        // Bursts carry on into the next register
+        let mut buf = [0; DATA_BUFFER_SIZE];
+        pmic.write_buffer(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]).unwrap();
+        pmic.read_buffer(&mut buf).unwrap();
+        assert_eq!(buf, [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+
+        // Only the IRQs written as one get cleared
+        pmic.device.raise_irq(Irq::TIMER | Irq::VBUS_PLUGGED);
+        pmic.clear_irq(Irq::TIMER).unwrap();
+        assert_eq!(pmic.irq_status().unwrap(), Irq::VBUS_PLUGGED);
+
+        pmic.device.set_adc(Channel::BatteryVoltage, 2800);
+        pmic.device.set_adc(Channel::BatteryPower, 0x12_3456);
+        assert_eq!(pmic.battery_voltage().unwrap(), MilliVolts(3080));
+        assert_eq!(pmic.adc_raw(Channel::BatteryPower).unwrap(), 0x12_3456);
+    }
+
+    #[test]
+    fn sim_adc() {
+        let mut pmic = Axp209::new(sim::Sim::new());
+
+        pmic.set_adc_control(AdcControl::all()).unwrap();
+        pmic.device.set_adc(Channel::AcinVoltage, 3000);
+        pmic.device.set_adc(Channel::AcinCurrent, 800);
+        pmic.device.set_adc(Channel::VbusVoltage, 3000);
+        pmic.device.set_adc(Channel::VbusCurrent, 1000);
+        pmic.device.set_adc(Channel::BatteryVoltage, 3500);
+        pmic.device.set_adc(Channel::BatteryChargeCurrent, 600);
+        pmic.device.set_adc(Channel::Temperature, 2000);
+
+        assert_eq!(pmic.acin_voltage().unwrap(), MilliVolts(5100));
+        assert_eq!(pmic.acin_current().unwrap(), MilliAmps(500));
+        assert_eq!(pmic.vbus_voltage().unwrap(), MilliVolts(5100));
+        assert_eq!(pmic.vbus_current().unwrap(), MilliAmps(375));
+        assert_eq!(pmic.battery_voltage().unwrap(), MilliVolts(3850));
+        assert_eq!(pmic.battery_charging_current().unwrap(), MilliAmps(300));
+        assert_eq!(pmic.temperature().unwrap(), DeciCelsius(553));
+
+        // One burst read agrees with all the single ones
+        let snapshot = pmic.adc_snapshot().unwrap();
+        assert_eq!(snapshot.acin_voltage, Some(pmic.acin_voltage().unwrap()));
+        assert_eq!(snapshot.vbus_current, Some(pmic.vbus_current().unwrap()));
+        assert_eq!(snapshot.battery_voltage, Some(pmic.battery_voltage().unwrap()));
+        assert_eq!(snapshot.battery_charging_current, Some(pmic.battery_charging_current().unwrap()));
+        assert_eq!(snapshot.temperature, Some(pmic.temperature().unwrap()));
+    }
+
+    #[test]
+    fn sim_timer_and_boot() {
+        let mut pmic = Axp209::new(sim::Sim::new());
+
+        pmic.modify_timer_control(|x| x.set_minutes(5)).unwrap();
+        pmic.device.expire_timer();
+        let timer = pmic.timer_control().unwrap();
+        assert_eq!((timer.minutes(), timer.expired()), (5, true));
+
+        // Changing the minutes leaves the expired flag alone, restarting
+        // clears it
+        pmic.modify_timer_control(|x| x.set_minutes(10)).unwrap();
+        assert!(pmic.timer_control().unwrap().expired());
+        pmic.modify_timer_control(|x| x.set_expired(true)).unwrap();
+        let timer = pmic.timer_control().unwrap();
+        assert_eq!((timer.minutes(), timer.expired()), (10, false));
+
+        // Records survive in the data buffer
+        assert_eq!(pmic.load_record::<BootCounter>().unwrap(), None);
+        pmic.store_record(&BootCounter { count: 3 }).unwrap();
+        assert_eq!(pmic.load_record::<BootCounter>().unwrap(), Some(BootCounter { count: 3 }));
+
+        // The boot reason is only given once, and other IRQs are left be
+        pmic.device.set_register(Registers::PowerStatus as u8, PowerStatus::START_ON_POWER.bits());
+        pmic.device.raise_irq(Irq::VBUS_PLUGGED | Irq::APS_LOW_LEVEL1);
+        assert_eq!(pmic.boot_reason().unwrap(), BootReason::VbusInserted);
+        assert_eq!(pmic.boot_reason().unwrap(), BootReason::WarmReboot);
+        assert_eq!(pmic.irq_status().unwrap(), Irq::APS_LOW_LEVEL1);
+    }
+
+    #[test]
+    fn mock_traffic() {
+        use mock::{Mock, Transaction};
+
+        let expected = [
+            Transaction::write(0x34, &[0x12, 0x5f]),
+            Transaction::write_read(0x34, &[0x12], &[0x5f]),
+            Transaction::write(0x34, &[0x8a, 0x85]),
+            Transaction::write_read(0x34, &[0x82], &[0x83, 0x80]),
+            Transaction::write(0x34, &[0x82, 0xf0, 0x01]),
+        ];
+        let mut pmic = Axp209::new(Mock::new(&expected));
+
+        pmic.set_power_control(PowerControl::new(0x5f)).unwrap();
+        pmic.set_timer_control(TimerControl::new(0x85)).unwrap();
+        let adc = pmic.adc_control().unwrap();
+        assert_eq!(adc.bits(), 0x8380);
+        pmic.set_adc_control(AdcControl::new(0xf001)).unwrap();
+
+        pmic.into_inner().done();
+    }
+
+    #[test]
+    fn fault_injection() {
+        use fault::{Fault, FaultError, FaultKind, Faulty};
+
+        let mut sim = sim::Sim::new();
+        sim.set_register(Registers::BatteryLevel as u8, 80);
+        sim.set_adc(Channel::BatteryVoltage, 3500);
+
+        let mut pmic = Axp209::new(Faulty::new(sim));
+        pmic.device.inject(Fault::new(FaultKind::Nak).on_register(0xb9).after(1).times(1));
+        pmic.device.inject(Fault::new(FaultKind::Stuck(0x55)).on_register(0x79).times(1));
+        pmic.device.inject(Fault::new(FaultKind::ArbitrationLoss).on_register(0x12).times(1));
+
+        assert_eq!(pmic.battery_level(), Ok(80));
+        assert_eq!(pmic.battery_level(), Err(Error::I2c(FaultError::Nak)));
+        assert_eq!(pmic.battery_level(), Ok(80));
+
+        // Only the low byte gets stuck, and only the once
+        assert_eq!(pmic.adc_raw(Channel::BatteryVoltage), Ok(0xda5));
+        assert_eq!(pmic.adc_raw(Channel::BatteryVoltage), Ok(3500));
+
+        assert_eq!(pmic.set_power_control(PowerControl::empty()),
+                   Err(Error::I2c(FaultError::ArbitrationLoss)));
+        assert!(pmic.device.is_spent());
+
+        // A fault that's used up from the start never strikes
+        pmic.device.inject(Fault::new(FaultKind::Nak).times(0));
+        assert!(pmic.device.is_spent());
+        assert_eq!(pmic.battery_level(), Ok(80));
+        assert_eq!(pmic.into_inner().into_inner().register(0x12), 0x5f);
+    }
+
+    #[test]
+    fn retry_policy() {
+        use fault::{Fault, FaultError, FaultKind, Faulty};
+        use hal::blocking::delay::DelayUs;
+
+        struct Waited(u32);
+
+        impl DelayUs<u32> for Waited {
+            fn delay_us(&mut self, us: u32) {
+                self.0 += us;
+            }
+        }
+
+        assert_eq!(RetryPolicy::<FaultError<sim::SimError>>::new(0, 100).attempts(), 1);
+
+        let policy = RetryPolicy::new(3, 100).retry_if(|e| *e == FaultError::Nak);
+        let mut pmic = Axp209::new(Retry::new(Faulty::new(sim::Sim::new()), Waited(0), policy));
+
+        // Two NAKs in a row still get through on the third go
+        pmic.device.bus_mut().inject(Fault::new(FaultKind::Nak).times(2));
+        assert_eq!(pmic.battery_level(), Ok(0x7f));
+        assert_eq!(pmic.device.delay_mut().0, 300);
+
+        // Three don't
+        pmic.device.bus_mut().inject(Fault::new(FaultKind::Nak).times(3));
+        assert_eq!(pmic.battery_level(), Err(Error::I2c(FaultError::Nak)));
+
+        // Nor does anything we didn't say to retry
+        pmic.device.bus_mut().clear();
+        pmic.device.bus_mut().inject(Fault::new(FaultKind::ArbitrationLoss).times(1));
+        assert_eq!(pmic.battery_level(), Err(Error::I2c(FaultError::ArbitrationLoss)));
+
+        // Restarting the timer only gets one go, in case it did restart
+        let waited = pmic.device.delay_mut().0;
+        pmic.device.bus_mut().inject(Fault::new(FaultKind::Nak).on_register(0x8a).times(1));
+        assert_eq!(pmic.set_8bit_register(0x8a, 0x80 | 5), Err(Error::I2c(FaultError::Nak)));
+        assert_eq!(pmic.device.delay_mut().0, waited);
+        // Only setting the minutes is fine to do again
+        pmic.device.bus_mut().inject(Fault::new(FaultKind::Nak).on_register(0x8a).times(1));
+        pmic.set_8bit_register(0x8a, 5).unwrap();
+        assert_eq!(pmic.device.delay_mut().0, waited + 100);
+
+        // A rail that didn't switch gets noticed
+        pmic.device.bus_mut().inject(Fault::new(FaultKind::Stuck(0x5f)).on_register(0x12).times(1));
+        assert_eq!(pmic.set_power_control(PowerControl::LDO3),
+                   Err(Error::VerifyFailed { register: 0x12, wrote: 0x40, read: 0x5f }));
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::LDO3);
+    }
+
+    #[test]
+    fn modify_keeps_reserved_bits() {
+        use fault::{Fault, FaultKind, Faulty};
+
+        let mut sim = sim::Sim::new();
+        sim.set_register(Registers::PowerControl as u8, 0xa0 | PowerControl::DCDC2.bits());
+        sim.set_register(Registers::AdcSampleTs as u8, 0b0000_1000);
+        sim.set_register(Registers::TimerControl as u8, 0x80 | 10);
+        sim.set_register(0x23, 0xc0 | 20);
+        sim.set_register(0x28, 0x35);
+
+        let mut pmic = Axp209::new(Faulty::new(sim));
+        pmic.set_verify_writes(true);
+
+        // Even built from scratch, the reserved bits survive
+        pmic.modify_power_control(|pc| *pc = PowerControl::LDO3).unwrap();
+        assert_eq!(pmic.power_control().unwrap().bits(), 0xa0 | PowerControl::LDO3.bits());
+
+        pmic.modify_adc_sample_ts(|ts| ts.set_sample_rate(SampleRate::Hz200)).unwrap();
+        assert_eq!(pmic.adc_sample_ts().unwrap().bits(), 0b1100_1000);
+
+        // The expired flag doesn't get cleared by changing the minutes
+        pmic.modify_timer_control(|t| t.set_minutes(20)).unwrap();
+        assert_eq!(pmic.timer_control().unwrap().bits(), 0x80 | 20);
+
+        // Nor do DCDC2's reserved bits, or LDO4 when LDO2 changes
+        pmic.modify_rail_voltage(Rail::Dcdc2, |v| *v += MilliVolts(100)).unwrap();
+        assert_eq!(pmic.get_8bit_register(0x23).unwrap(), 0xc0 | 24);
+        pmic.modify_rail_voltage(Rail::Ldo2, |v| *v = MilliVolts(3300)).unwrap();
+        assert_eq!(pmic.get_8bit_register(0x28).unwrap(), 0xf5);
+        assert_eq!(pmic.modify_rail_voltage(Rail::Ldo2, |v| *v = MilliVolts(3400)),
+                   Err(Error::VoltageOutOfRange(Rail::Ldo2, MilliVolts(3400))));
+        assert_eq!(pmic.get_8bit_register(0x28).unwrap(), 0xf5);
+        assert_eq!(pmic.modify_rail_voltage(Rail::Exten, |_| {}), Err(Error::NoVoltage(Rail::Exten)));
+
+        pmic.modify_irq_enable(|irq| irq.insert(Irq::TIMER)).unwrap();
+        assert!(pmic.irq_enable().unwrap().contains(Irq::TIMER | Irq::VBUS_PLUGGED));
+
+        pmic.device.inject(Fault::new(FaultKind::BitFlip(0x04)).on_register(0x8f).after(1).times(1));
+        assert_eq!(pmic.modify_over_temperature_control(|ot| ot.insert(OverTemperatureControl::SHUTDOWN)),
+                   Err(Error::VerifyFailed { register: 0x8f, wrote: 0x04, read: 0x00 }));
+    }
+
+    #[test]
+    fn rail_voltages() {
+        let ldo4 = Rail::Ldo4.voltage_field().unwrap();
+        let dcdc3 = Rail::Dcdc3.voltage_field().unwrap();
+
+        assert_eq!(ldo4.encode(MilliVolts(2600)), Some(9));
+        assert_eq!(ldo4.decode(0xf9), MilliVolts(2500));
+        assert_eq!(dcdc3.encode(MilliVolts(1212)), Some(20));
+        assert_eq!(dcdc3.encode(MilliVolts(600)), None);
+        assert_eq!(dcdc3.decode(20), MilliVolts(1200));
+        assert!(Rail::Exten.voltage_field().is_none());
+
+        let mut pmic = Axp209::new(sim::Sim::new());
+        pmic.set_rail_voltage(Rail::Ldo2, MilliVolts(3300)).unwrap();
+        pmic.set_rail_voltage(Rail::Ldo4, MilliVolts(1800)).unwrap();
+        assert_eq!(pmic.rail_voltage(Rail::Ldo2).unwrap(), MilliVolts(3300));
+        assert_eq!(pmic.device.register(0x28), 0xf6);
+
+        // Nothing gets written for voltages a rail can't do
+        assert_eq!(pmic.set_rail_voltage(Rail::Ldo2, MilliVolts(5000)),
+                   Err(Error::VoltageOutOfRange(Rail::Ldo2, MilliVolts(5000))));
+        assert_eq!(pmic.set_rail_voltage(Rail::Exten, MilliVolts(5000)), Err(Error::NoVoltage(Rail::Exten)));
+        assert_eq!(pmic.rail_voltage(Rail::Exten), Err(Error::NoVoltage(Rail::Exten)));
+        assert_eq!(pmic.device.register(0x28), 0xf6);
+    }
+
+    #[test]
+    fn rail_policy_enforcement() {
+        let policy = RailPolicy::new()
+            .critical(Rail::Dcdc3)
+            .locked(Rail::Ldo2)
+            .window(Rail::Dcdc2, MilliVolts(1100), MilliVolts(1400));
+        let on = PowerControl::DCDC3 | PowerControl::LDO2 | PowerControl::DCDC2;
+
+        let mut sim = sim::Sim::new();
+        sim.set_register(Registers::PowerControl as u8, on.bits());
+        let mut pmic = Axp209::with_rail_policy(sim, policy);
+
+        assert_eq!(pmic.set_power_control(on - PowerControl::DCDC3),
+                   Err(Error::RailPolicy(PolicyViolation::Critical(Rail::Dcdc3))));
+        assert_eq!(pmic.modify_power_control(|pc| pc.remove(PowerControl::LDO2)),
+                   Err(Error::RailPolicy(PolicyViolation::Locked(Rail::Ldo2))));
+        assert_eq!(pmic.set_rail_voltage(Rail::Dcdc2, MilliVolts(1500)),
+                   Err(Error::RailPolicy(PolicyViolation::OutOfWindow(Rail::Dcdc2, MilliVolts(1500)))));
+        assert_eq!(pmic.device.register(Registers::PowerControl as u8), on.bits());
+
+        assert_eq!(pmic.modify_rail_voltage(Rail::Dcdc2, |v| *v += MilliVolts(1000)),
+                   Err(Error::RailPolicy(PolicyViolation::OutOfWindow(Rail::Dcdc2, MilliVolts(1700)))));
+
+        pmic.set_rail_voltage(Rail::Dcdc2, MilliVolts(1200)).unwrap();
+        pmic.modify_power_control(|pc| pc.remove(PowerControl::DCDC2)).unwrap();
+
+        // Unless we really mean it
+        pmic.without_rail_policy(|p| p.set_power_control(PowerControl::empty())).unwrap();
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::empty());
+    }
+
+    #[test]
+    fn board_profiles() {
+        use boards::{Connection, Function, Output, CHIP};
+
+        assert_eq!(CHIP.flags(Function::Wifi), PowerControl::LDO3 | PowerControl::LDO4);
+        assert!(CHIP.connection(Output::Rail(Rail::Dcdc3)).unwrap().critical);
+
+        let mut sim = sim::Sim::new();
+        sim.set_register(Registers::PowerControl as u8, 0x5f);
+        let mut pmic = Axp209::with_rail_policy(sim, CHIP.rail_policy());
+
+        let wifi = CHIP.rail(Function::Wifi);
+        wifi.disable(&mut pmic).unwrap();
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::all() - PowerControl::LDO3 - PowerControl::LDO4);
+        assert!(!wifi.is_enabled(&mut pmic).unwrap());
+        assert_eq!(pmic.set_rail_enabled(Rail::Dcdc3, false),
+                   Err(Error::RailPolicy(PolicyViolation::Critical(Rail::Dcdc3))));
+        assert!(pmic.set_rail_voltage(Rail::Dcdc2, MilliVolts(1500)).is_err());
+        assert_eq!(CHIP.rail(Function::Cpu).disable(&mut pmic),
+                   Err(Error::RailPolicy(PolicyViolation::Critical(Rail::Dcdc2))));
+
+        // A board that powers its WiFi from LDO3 and the GPIO LDO together
+        const GPIO_BOARD: Board = Board {
+            name: "GPIO board",
+            connections: &[
+                Connection {
+                    output: Output::Rail(Rail::Ldo3),
+                    function: Function::Wifi,
+                    voltage: (MilliVolts(3300), MilliVolts(3300)),
+                    critical: false,
+                },
+                Connection {
+                    output: Output::GpioLdo,
+                    function: Function::Wifi,
+                    voltage: (MilliVolts(3300), MilliVolts(3300)),
+                    critical: false,
+                },
+            ],
+        };
+
+        let mut sim = sim::Sim::new();
+        sim.set_register(Registers::PowerControl as u8, 0);
+        sim.set_register(Registers::Gpio0Control as u8, 0x20 | GPIO0_FLOATING);
+        let mut pmic = Axp209::new(sim);
+        let wifi = GPIO_BOARD.rail(Function::Wifi);
+
+        pmic.set_gpio_ldo_voltage(MilliVolts(3300)).unwrap();
+        assert_eq!(pmic.gpio_ldo_voltage().unwrap(), MilliVolts(3300));
+        assert_eq!(pmic.set_gpio_ldo_voltage(MilliVolts(1700)), Err(Error::GpioVoltageOutOfRange(MilliVolts(1700))));
+
+        wifi.enable(&mut pmic).unwrap();
+        assert!(wifi.is_enabled(&mut pmic).unwrap());
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::LDO3);
+        assert_eq!(pmic.device.register(Registers::Gpio0Control as u8), 0x20 | GPIO0_LDO);
+
+        wifi.disable(&mut pmic).unwrap();
+        assert!(!pmic.gpio_ldo_enabled().unwrap());
+        assert_eq!(pmic.device.register(Registers::Gpio0Control as u8), 0x20 | GPIO0_FLOATING);
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::empty());
+
+        assert!(boards::BOARDS.iter().any(|x| x.name == boards::C64_MINI.name));
+    }
+
+    #[test]
+    fn shared_regulators() {
+        let mut sim = sim::Sim::new();
+        sim.set_register(Registers::PowerControl as u8, 0);
+        let mut pmic = Axp209::new(sim);
+
+        let wifi = pmic.regulator(Rail::Ldo3).unwrap();
+        let sensor = pmic.regulator(Rail::Ldo3).unwrap();
+
+        wifi.enable(&mut pmic).unwrap();
+        sensor.enable(&mut pmic).unwrap();
+        wifi.disable(&mut pmic).unwrap();
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::LDO3);
+        sensor.disable(&mut pmic).unwrap();
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::empty());
+
+        // Enables are counted, and a disable too many does nothing
+        wifi.enable(&mut pmic).unwrap();
+        wifi.enable(&mut pmic).unwrap();
+        assert_eq!(wifi.enable_count(&pmic), 2);
+        wifi.disable(&mut pmic).unwrap();
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::LDO3);
+        wifi.disable(&mut pmic).unwrap();
+        wifi.disable(&mut pmic).unwrap();
+        assert!(!wifi.is_enabled(&pmic));
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::empty());
+
+        wifi.set_voltage(&mut pmic, MilliVolts(3000), MilliVolts(3300)).unwrap();
+        sensor.set_voltage(&mut pmic, MilliVolts(3110), MilliVolts(3500)).unwrap();
+        assert_eq!(pmic.rail_voltage(Rail::Ldo3).unwrap(), MilliVolts(3125));
+        assert_eq!(sensor.set_voltage(&mut pmic, MilliVolts(3400), MilliVolts(3500)),
+                   Err(Error::VoltageConflict(Rail::Ldo3)));
+
+        // The refused request didn't stick
+        wifi.set_voltage(&mut pmic, MilliVolts(2000), MilliVolts(3300)).unwrap();
+        assert_eq!(pmic.rail_voltage(Rail::Ldo3).unwrap(), MilliVolts(3125));
+
+        // Releasing drops every enable at once
+        sensor.enable(&mut pmic).unwrap();
+        sensor.enable(&mut pmic).unwrap();
+        sensor.release(&mut pmic).unwrap();
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::empty());
+
+        for _ in 1..regulator::MAX_CONSUMERS {
+            pmic.regulator(Rail::Ldo3).unwrap();
+        }
+        assert_eq!(pmic.regulator(Rail::Ldo3).unwrap_err(), Error::NoConsumerSlots(Rail::Ldo3));
+    }
+
+    #[test]
+    fn power_sequencing() {
+        use fault::{Fault, FaultError, FaultKind, Faulty};
+        use hal::blocking::delay::DelayMs;
+
+        struct Waited(u16);
+
+        impl DelayMs<u16> for Waited {
+            fn delay_ms(&mut self, ms: u16) {
+                self.0 += ms;
+            }
+        }
+
+        const STEPS: &[Step] = &[
+            Step::rail(Rail::Dcdc2).voltage(MilliVolts(1200)).wait(5),
+            Step::rail(Rail::Ldo3).voltage(MilliVolts(3300)).wait(5),
+            Step::rail(Rail::Exten).wait(5),
+        ];
+        let sequence = PowerSequence::new(STEPS);
+
+        let mut sim = sim::Sim::new();
+        sim.set_register(Registers::PowerControl as u8, PowerControl::DCDC3.bits());
+        let mut pmic = Axp209::new(Faulty::new(sim));
+        let mut delay = Waited(0);
+
+        sequence.power_up(&mut pmic, &mut delay).unwrap();
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::all() - PowerControl::LDO2 - PowerControl::LDO4);
+        assert_eq!(pmic.rail_voltage(Rail::Dcdc2).unwrap(), MilliVolts(1200));
+        assert_eq!(delay.0, 15);
+
+        sequence.power_down(&mut pmic, &mut delay).unwrap();
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::DCDC3);
+
+        // LDO3 doesn't come up, so DCDC2 goes back how it was
+        pmic.set_rail_voltage(Rail::Dcdc2, MilliVolts(1000)).unwrap();
+        pmic.device.inject(Fault::new(FaultKind::Nak).on_register(0x29).after(1).times(1));
+        let error = sequence.power_up(&mut pmic, &mut delay).unwrap_err();
+        assert_eq!(error, SequenceError { step: 1, error: Error::I2c(FaultError::Nak), rolled_back: true });
+        assert_eq!(pmic.power_control().unwrap(), PowerControl::DCDC3);
+        assert_eq!(pmic.rail_voltage(Rail::Dcdc2).unwrap(), MilliVolts(1000));
+    }
+
+    #[test]
+    fn fuel_gauge_survives_faults() {
+        use fault::{Fault, FaultKind, Faulty};
+
+        let mut sim = sim::Sim::new();
+        sim.set_adc(Channel::BatteryVoltage, 3500);
+
+        let mut gauge = FuelGauge::new(MilliAmpHours(2000), &LIPO_OCV).unwrap();
+        let mut pmic = Axp209::new(Faulty::new(sim));
+        let before = pmic.update_fuel_gauge(&mut gauge).unwrap();
+
+        // Halfway through gathering a sample
+        pmic.device.inject(Fault::new(FaultKind::Nak).on_register(0x7a).times(1));
+        assert!(pmic.update_fuel_gauge(&mut gauge).is_err());
+        assert_eq!(gauge.estimate(), before);
+    }
+
+    #[test]
+    fn battery_state_decoding() {
+        let usb = PowerStatus::VBUS_PRESENT | PowerStatus::VBUS_USABLE;
+        let present = ChargingStatus::BATTERY_PRESENT;
+        let on = ChargeControl::ENABLE;
+        let off = ChargeControl::empty();
+
+        assert_eq!(BatteryState::new(usb, ChargingStatus::empty(), on, 50), BatteryState::Absent);
+        assert_eq!(BatteryState::new(usb, present, on, BATTERY_LEVEL_MISSING), BatteryState::Absent);
+        assert_eq!(BatteryState::new(usb, present | ChargingStatus::OVERTEMPERATURE | ChargingStatus::CHARGING, on, 50),
+                   BatteryState::Fault);
+        assert_eq!(BatteryState::new(usb, present | ChargingStatus::CELL_ACTIVATION_MODE, on, 0),
+                   BatteryState::ActivationMode);
+        assert_eq!(BatteryState::new(usb | PowerStatus::DISCHARGING, present | ChargingStatus::CHARGING, on, 50),
+                   BatteryState::Charging);
+        assert_eq!(BatteryState::new(usb, present, on, 100), BatteryState::Full);
+        // Between charge cycles, or with the charger switched off
+        assert_eq!(BatteryState::new(usb | PowerStatus::DISCHARGING, present, on, 98), BatteryState::NotCharging);
+        assert_eq!(BatteryState::new(usb, present, off, 100), BatteryState::NotCharging);
+        assert_eq!(BatteryState::new(PowerStatus::empty(), present, on, 98), BatteryState::Discharging);
+        assert_eq!(BatteryState::new(PowerStatus::empty(), present, on, 100), BatteryState::Discharging);
+
+        // Half full on USB with the charger off isn't full
+        let mut pmic = Axp209::new(sim::Sim::new());
+        pmic.device.set_register(Registers::PowerStatus as u8, usb.bits());
+        pmic.device.set_register(Registers::ChargingStatus as u8, present.bits());
+        pmic.device.set_register(Registers::BatteryLevel as u8, 50);
+        pmic.modify_charge_control(|x| x.remove(ChargeControl::ENABLE)).unwrap();
+        assert_eq!(pmic.battery_state().unwrap(), BatteryState::NotCharging);
+        pmic.device.set_register(Registers::BatteryLevel as u8, 100);
+        assert_eq!(pmic.battery_state().unwrap(), BatteryState::NotCharging);
+        pmic.modify_charge_control(|x| x.insert(ChargeControl::ENABLE)).unwrap();
+        assert_eq!(pmic.battery_state().unwrap(), BatteryState::Full);
+
+        // The level register says 0x7f when there's no battery
+        let mut pmic = Axp209::new(sim::Sim::new());
+        assert!(!pmic.battery_present().unwrap());
+        pmic.device.set_register(Registers::BatteryLevel as u8, 100);
+        assert!(pmic.battery_present().unwrap());
+        pmic.device.set_register(Registers::BatteryLevel as u8, 0);
+        assert!(pmic.battery_present().unwrap());
+    }
+
+    #[test]
+    fn battery_time_estimates() {
+        let mut average = battery_time::CurrentAverage::default();
+        let capacity = MilliAmpHours(2000);
+
+        // A spike doesn't throw the average far off
+        assert_eq!(average.update(MilliAmps(0), MilliAmps(500)), -500);
+        assert_eq!(average.update(MilliAmps(0), MilliAmps(1300)), -600);
+
+        // And it settles right on a steady current, not a few mA short
+        average.reset();
+        average.update(MilliAmps(0), MilliAmps(0));
+        for _ in 0..100 {
+            average.update(MilliAmps(307), MilliAmps(0));
+        }
+        assert_eq!(average.update(MilliAmps(307), MilliAmps(0)), 307);
+
+        assert_eq!(battery_time::time_to_empty(50, capacity, -500), Some(Duration::from_secs(7200)));
+        assert_eq!(battery_time::time_to_empty(50, capacity, 100), None);
+        assert_eq!(battery_time::time_to_full(50, capacity, 2000, MilliAmps(500)),
+                   Some(Duration::from_secs(7200)));
+
+        // Out of range charge currents are clamped rather than panicking
+        let mut charge = ChargeControl::new(0);
+        charge.set_current(MilliAmps(5000));
+        assert_eq!(charge.current(), MilliAmps(1800));
+        charge.set_current(MilliAmps(0));
+        assert_eq!(charge.current(), MilliAmps(300));
+
+        // Both estimates come from one reading, 500mA out of a half full
+        // 2000mAh battery
+        let mut pmic = Axp209::new(sim::Sim::new());
+        pmic.device.set_register(Registers::BatteryLevel as u8, 50);
+        pmic.device.set_adc(Channel::BatteryDischargeCurrent, 1000);
+        assert_eq!(pmic.update_battery_times().unwrap(), BatteryTimes::default());
+
+        pmic.set_battery_capacity(capacity);
+        assert_eq!(pmic.update_battery_times().unwrap(),
+                   BatteryTimes { to_empty: Some(Duration::from_secs(7200)), to_full: None });
+
+        // Reading them doesn't feed the average again
+        pmic.device.set_adc(Channel::BatteryDischargeCurrent, 0);
+        assert_eq!(pmic.time_to_empty(), Some(Duration::from_secs(7200)));
+        assert_eq!(pmic.time_to_full(), None);
+        assert_eq!(pmic.battery_times().to_empty, pmic.time_to_empty());
+    }
+
     #[test]
     // Because I don't trust the binary math I did here
     fn adc_control_rate_setting() {
@@ -0,0 +1,134 @@
+//! A fake I2C bus that only accepts exactly the traffic it's told to
+//! expect. Where `sim` checks that the driver ends up with the right
+//! answer, this checks how it got there, byte for byte:
+//!
+//! ```ignore
+//! let expected = [
+//!     Transaction::write(0x34, &[0x12, 0x5f]),
+//!     Transaction::write_read(0x34, &[0x8a], &[0x80]),
+//! ];
+//! let mut pmic = Axp209::new(Mock::new(&expected));
+//!
+//! pmic.set_power_control(PowerControl::new(0x5f))?;
+//! pmic.timer_control()?;
+//! pmic.into_inner().done();
+//! ```
+//!
+//! Anything that doesn't match the next expected transaction panics with
+//! what was expected and what actually happened, which is what a test
+//! wants. Call `done()` at the end to check nothing was left out.
+//!
+//! Only built with the `sim` feature (and for the crate's own tests).
+
+use core::convert::Infallible;
+
+use hal::blocking::i2c::{Read, Write, WriteRead};
+
+/// One thing that's expected to happen on the bus
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Transaction<'a> {
+    /// Bytes sent to the device
+    Write { address: u8, bytes: &'a [u8] },
+    /// Bytes handed back by the device
+    Read { address: u8, bytes: &'a [u8] },
+    /// A write followed by a read without letting go of the bus
+    WriteRead { address: u8, write: &'a [u8], read: &'a [u8] },
+}
+
+impl<'a> Transaction<'a> {
+    pub fn write(address: u8, bytes: &'a [u8]) -> Self {
+        Transaction::Write { address, bytes }
+    }
+
+    pub fn read(address: u8, bytes: &'a [u8]) -> Self {
+        Transaction::Read { address, bytes }
+    }
+
+    pub fn write_read(address: u8, write: &'a [u8], read: &'a [u8]) -> Self {
+        Transaction::WriteRead { address, write, read }
+    }
+}
+
+pub struct Mock<'a> {
+    expected: &'a [Transaction<'a>],
+    /// How many of `expected` have happened so far
+    next: usize,
+}
+
+impl<'a> Mock<'a> {
+    pub fn new(expected: &'a [Transaction<'a>]) -> Self {
+        Mock {
+            expected,
+            next: 0,
+        }
+    }
+
+    /// Panic if any of the expected transactions never happened
+    pub fn done(&self) {
+        assert!(self.next == self.expected.len(),
+                "Only {} of {} transactions happened, next expected {:?}",
+                self.next, self.expected.len(), self.expected[self.next]);
+    }
+
+    /// Check the actual transaction against the next expected one and hand
+    /// back the expected one, which has the bytes to read in it
+    fn expect(&mut self, actual: Transaction) -> Transaction<'a> {
+        let expected = match self.expected.get(self.next) {
+            Some(x) => *x,
+            None => panic!("Unexpected transaction {:?}, all {} already happened",
+                           actual, self.expected.len()),
+        };
+
+        // The read buffers can't be compared, they're what we're filling in,
+        // so only their lengths get checked
+        let matches = match (expected, actual) {
+            (Transaction::Write { address: a, bytes: x },
+             Transaction::Write { address: b, bytes: y }) => a == b && x == y,
+            (Transaction::Read { address: a, bytes: x },
+             Transaction::Read { address: b, bytes: y }) => a == b && x.len() == y.len(),
+            (Transaction::WriteRead { address: a, write: x, read: r },
+             Transaction::WriteRead { address: b, write: y, read: s }) =>
+                a == b && x == y && r.len() == s.len(),
+            _ => false,
+        };
+
+        assert!(matches, "Transaction {} was {:?}, expected {:?}", self.next, actual, expected);
+
+        self.next += 1;
+        expected
+    }
+}
+
+impl<'a> Write for Mock<'a> {
+    type Error = Infallible;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.expect(Transaction::write(address, bytes));
+
+        Ok(())
+    }
+}
+
+impl<'a> Read for Mock<'a> {
+    type Error = Infallible;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        if let Transaction::Read { bytes, .. } = self.expect(Transaction::read(address, buffer)) {
+            buffer.copy_from_slice(bytes);
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> WriteRead for Mock<'a> {
+    type Error = Infallible;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        if let Transaction::WriteRead { read, .. } = self.expect(Transaction::write_read(address, bytes, buffer)) {
+            buffer.copy_from_slice(read);
+        }
+
+        Ok(())
+    }
+}
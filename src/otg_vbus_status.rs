@@ -0,0 +1,33 @@
+//! The USB OTG VBUS Status register reports what the chip sees on VBUS from
+//! a USB On-The-Go point of view. If you're running a USB gadget stack, this
+//! is how you find out a host has been plugged in.
+
+bitflags! {
+    /// Holds the state of the register. Bits 7 through 3 are reserved and
+    /// all bits here are read-only.
+    pub struct OtgVbusStatus: u8 {
+        /// Whether VBUS is above the OTG "VBUS valid" threshold, meaning
+        /// a host is supplying power
+        const VBUS_VALID = 1 << 2;
+        /// Whether VBUS is above the session valid threshold for either
+        /// an A or B device
+        const SESSION_VALID = 1 << 1;
+        /// Whether VBUS has dropped below the session end threshold
+        const SESSION_END = 1 << 0;
+    }
+}
+
+impl OtgVbusStatus {
+    /// No checks are made here, and it's expected that it be populated by
+    /// the raw value from the axp209 chip.
+    pub fn new(value: u8) -> Self {
+        Self {
+            bits: value
+        }
+    }
+
+    /// Whether a USB host looks to be attached.
+    pub fn host_attached(&self) -> bool {
+        self.contains(Self::VBUS_VALID | Self::SESSION_VALID)
+    }
+}
@@ -0,0 +1,39 @@
+//! Register 0x8F holds a handful of unrelated switches, the most important
+//! being whether the chip cuts power when it gets too hot. The
+//! `ChargingStatus::OVERTEMPERATURE` bit tells you when that point has been
+//! reached, but by then it's usually too late to do anything about it, so
+//! have a look at `Axp209::thermal_headroom()` if you want to throttle early.
+
+use units::DeciCelsius;
+
+/// The internal temperature at which the chip shuts itself down when
+/// `OverTemperatureControl::SHUTDOWN` is set.
+pub const OVERTEMPERATURE_TRIP_POINT: DeciCelsius = DeciCelsius(1450);
+
+bitflags! {
+    /// Holds the state of the register. Changes will need to be committed manually
+    pub struct OverTemperatureControl: u8 {
+        /// Whether a falling edge on the IRQ pin wakes the chip up from sleep
+        const IRQ_WAKEUP = 1 << 7;
+        /// Whether the chip powers off when the N_OE pin is pulled high.
+        /// When cleared N_OE is ignored once the system is up.
+        const NOE_SHUTDOWN = 1 << 6;
+        /// Whether VBUS is allowed to power the system when ACIN and VBUS
+        /// are shorted together on the board (see `PowerStatus::SHORT_CIRCUIT`)
+        const VBUS_ACIN_SHORTED = 1 << 3;
+        /// Whether the chip powers off when it passes
+        /// `OVERTEMPERATURE_TRIP_POINT`. This is on by default and turning it
+        /// off is asking for trouble.
+        const SHUTDOWN = 1 << 2;
+    }
+}
+
+impl OverTemperatureControl {
+    /// No checks are made here, and it's expected that it be populated by
+    /// the raw value from the axp209 chip.
+    pub fn new(value: u8) -> Self {
+        Self {
+            bits: value
+        }
+    }
+}
@@ -16,12 +16,16 @@ bitflags! {
         const VBUS_USABLE = 1 << 4;
         /// Whether the incoming voltage is above the configured VHOLD value.
         const VBUS_ABOVE_HOLD = 1 << 3;
-        /// State of the battery (Charging = true, Discharging = false)
+        /// Which way the battery current is flowing. Despite the name it's
+        /// set when current flows *into* the battery (Charging = true,
+        /// Discharging = false). Use `Axp209::battery_state()` rather than
+        /// reading this on its own.
         const DISCHARGING = 1 << 2;
         /// I'm not quite sure here. The datasheet says a short circuit between VBUS and ACIN
         const SHORT_CIRCUIT = 1 << 1; // A fine movie
-        /// If the chip powers on the system when power is appled. Not sure where this can be
-        /// set.
+        /// Whether the chip was powered on by ACIN or VBUS being plugged in, rather than
+        /// by the power key. It's decided by the hardware, so it can't be set. See
+        /// `Axp209::boot_reason()` for the full story.
         const START_ON_POWER = 1 << 0;
     }
 }
@@ -0,0 +1,170 @@
+//! The outputs of the chip, one at a time. `PowerControl` switches them all
+//! at once, this is for everything else: which bit switches a rail, which
+//! register holds its voltage and how that voltage is encoded.
+//!
+//! The voltage steps come straight from the datasheet:
+//!
+//! | Rail  | Register | Range          | Step            |
+//! |-------|----------|----------------|-----------------|
+//! | DCDC2 | 0x23     | 0.7V - 2.275V  | 25mV            |
+//! | DCDC3 | 0x27     | 0.7V - 3.5V    | 25mV            |
+//! | LDO2  | 0x28     | 1.8V - 3.3V    | 100mV           |
+//! | LDO3  | 0x29     | 0.7V - 3.5V    | 25mV            |
+//! | LDO4  | 0x28     | 1.25V - 3.3V   | a lookup table  |
+//!
+//! EXTEN is only a switch for something external, so it doesn't have a
+//! voltage.
+
+use core::fmt;
+
+use power_control::PowerControl;
+use units::MilliVolts;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Rail {
+    Dcdc2,
+    Dcdc3,
+    Ldo2,
+    Ldo3,
+    Ldo4,
+    Exten,
+}
+
+/// How many rails there are, for tables with one entry per rail
+pub const RAIL_COUNT: usize = 6;
+
+/// Every rail, in the same order as the enum
+pub const RAILS: [Rail; RAIL_COUNT] = [
+    Rail::Dcdc2,
+    Rail::Dcdc3,
+    Rail::Ldo2,
+    Rail::Ldo3,
+    Rail::Ldo4,
+    Rail::Exten,
+];
+
+/// GPIO0's voltage when it's an LDO (see `Axp209::set_gpio_ldo_voltage()`)
+pub const GPIO_LDO_VOLTAGE: VoltageField = VoltageField {
+    register: 0x91,
+    mask: 0b1111_0000,
+    min: MilliVolts(1800),
+    max: MilliVolts(3300),
+    step: 100,
+};
+
+/// LDO4 doesn't do even steps, its voltage is picked from this table
+const LDO4_VOLTAGES: [u16; 16] = [
+    1250, 1300, 1400, 1500, 1600, 1700, 1800, 1900,
+    2000, 2500, 2700, 2800, 3000, 3100, 3200, 3300,
+];
+
+/// Where a rail's voltage lives and how it's encoded
+#[derive(Debug, Clone, Copy)]
+pub struct VoltageField {
+    pub register: u8,
+    /// Which bits of the register, already shifted into place
+    pub mask: u8,
+    pub min: MilliVolts,
+    pub max: MilliVolts,
+    /// Zero for LDO4, which uses `LDO4_VOLTAGES` instead
+    step: u16,
+}
+
+impl VoltageField {
+    fn shift(&self) -> u32 {
+        self.mask.trailing_zeros()
+    }
+
+    /// Turn a voltage into the bits for the register, already shifted into
+    /// place. Voltages in between steps get rounded down, as that's the
+    /// safe way to round a voltage. `None` if it's out of range.
+    pub fn encode(&self, value: MilliVolts) -> Option<u8> {
+        if value < self.min || value > self.max {
+            return None;
+        }
+
+        // No step means it's LDO4 and its table
+        let code = match (value.0 - self.min.0).checked_div(self.step) {
+            Some(x) => x as u8,
+            None => LDO4_VOLTAGES.iter().rposition(|x| *x <= value.0).unwrap_or(0) as u8,
+        };
+
+        Some(code << self.shift())
+    }
+
+    /// The lowest voltage the rail can actually be set to that's at least
+    /// `value`, if there is one
+    pub fn round_up(&self, value: MilliVolts) -> Option<MilliVolts> {
+        if value > self.max {
+            return None;
+        }
+
+        let value = value.max(self.min);
+        let below = self.decode(self.encode(value)?);
+
+        if below == value {
+            return Some(value);
+        }
+
+        match self.step {
+            0 => LDO4_VOLTAGES.iter().find(|x| **x >= value.0).map(|x| MilliVolts(*x)),
+            step => Some(below + MilliVolts(step)).filter(|x| *x <= self.max),
+        }
+    }
+
+    /// Pull the voltage out of a register value
+    pub fn decode(&self, register: u8) -> MilliVolts {
+        let code = (register & self.mask) >> self.shift();
+
+        if self.step == 0 {
+            MilliVolts(LDO4_VOLTAGES[code as usize])
+        } else {
+            MilliVolts(self.min.0 + code as u16 * self.step)
+        }
+    }
+}
+
+impl Rail {
+    /// The `PowerControl` bit that switches this rail
+    pub fn flag(&self) -> PowerControl {
+        match *self {
+            Rail::Dcdc2 => PowerControl::DCDC2,
+            Rail::Dcdc3 => PowerControl::DCDC3,
+            Rail::Ldo2 => PowerControl::LDO2,
+            Rail::Ldo3 => PowerControl::LDO3,
+            Rail::Ldo4 => PowerControl::LDO4,
+            Rail::Exten => PowerControl::EXTEN,
+        }
+    }
+
+    /// Where the voltage is set, for rails that have one
+    pub fn voltage_field(&self) -> Option<VoltageField> {
+        let field = |register, mask, min, max, step| {
+            Some(VoltageField { register, mask, min: MilliVolts(min), max: MilliVolts(max), step })
+        };
+
+        match *self {
+            Rail::Dcdc2 => field(0x23, 0b0011_1111, 700, 2275, 25),
+            Rail::Dcdc3 => field(0x27, 0b0111_1111, 700, 3500, 25),
+            Rail::Ldo2 => field(0x28, 0b1111_0000, 1800, 3300, 100),
+            Rail::Ldo3 => field(0x29, 0b0111_1111, 700, 3500, 25),
+            Rail::Ldo4 => field(0x28, 0b0000_1111, 1250, 3300, 0),
+            Rail::Exten => None,
+        }
+    }
+}
+
+impl fmt::Display for Rail {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Rail::Dcdc2 => "DCDC2",
+            Rail::Dcdc3 => "DCDC3",
+            Rail::Ldo2 => "LDO2",
+            Rail::Ldo3 => "LDO3",
+            Rail::Ldo4 => "LDO4",
+            Rail::Exten => "EXTEN",
+        };
+
+        f.write_str(name)
+    }
+}
@@ -0,0 +1,141 @@
+//! Rules about which rails can be touched, so a stray `set_power_control()`
+//! can't switch off the SoC's core voltage. On the NTC C.H.I.P. that's
+//! DCDC3, and turning it off doesn't end well.
+//!
+//! A policy is handed over with `Axp209::with_rail_policy()` and checked
+//! before anything gets written, so a write that breaks it never reaches
+//! the bus:
+//!
+//! ```ignore
+//! let policy = RailPolicy::new()
+//!     .critical(Rail::Dcdc3)
+//!     .window(Rail::Dcdc2, MilliVolts(1100), MilliVolts(1400))
+//!     .locked(Rail::Ldo2);
+//! let mut pmic = Axp209::with_rail_policy(i2c, policy);
+//! ```
+//!
+//! When turning things off really is the plan, like powering down, use
+//! `Axp209::without_rail_policy()`.
+
+use core::fmt;
+
+use power_control::PowerControl;
+use rail::{Rail, RAILS, RAIL_COUNT};
+use units::MilliVolts;
+
+/// What was wrong with a write
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PolicyViolation {
+    /// It would have turned off a critical rail
+    Critical(Rail),
+    /// It would have changed a locked rail
+    Locked(Rail),
+    /// It would have set a voltage outside the rail's window
+    OutOfWindow(Rail, MilliVolts),
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PolicyViolation::Critical(rail) => write!(f, "{} is critical and can't be turned off", rail),
+            PolicyViolation::Locked(rail) => write!(f, "{} is locked", rail),
+            PolicyViolation::OutOfWindow(rail, voltage) => write!(f, "{} isn't allowed on {}", voltage, rail),
+        }
+    }
+}
+
+/// The rules for one rail
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+struct Rule {
+    critical: bool,
+    locked: bool,
+    window: Option<(MilliVolts, MilliVolts)>,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct RailPolicy {
+    rules: [Rule; RAIL_COUNT],
+}
+
+impl RailPolicy {
+    /// A policy that allows anything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The rail can be turned on, and have its voltage changed, but never
+    /// turned off
+    pub fn critical(mut self, rail: Rail) -> Self {
+        self.rules[rail as usize].critical = true;
+        self
+    }
+
+    /// The rail can't be changed at all, on, off or voltage
+    pub fn locked(mut self, rail: Rail) -> Self {
+        self.rules[rail as usize].locked = true;
+        self
+    }
+
+    /// The rail's voltage has to stay between `min` and `max`, inclusive
+    pub fn window(mut self, rail: Rail, min: MilliVolts, max: MilliVolts) -> Self {
+        self.rules[rail as usize].window = Some((min, max));
+        self
+    }
+
+    pub fn is_critical(&self, rail: Rail) -> bool {
+        self.rules[rail as usize].critical
+    }
+
+    pub fn is_locked(&self, rail: Rail) -> bool {
+        self.rules[rail as usize].locked
+    }
+
+    pub fn window_of(&self, rail: Rail) -> Option<(MilliVolts, MilliVolts)> {
+        self.rules[rail as usize].window
+    }
+
+    /// Whether switching outputs needs checking at all
+    pub fn guards_outputs(&self) -> bool {
+        self.rules.iter().any(|x| x.critical || x.locked)
+    }
+
+    /// Whether setting a rail's voltage needs checking at all
+    pub fn guards_voltage(&self, rail: Rail) -> bool {
+        let rule = self.rules[rail as usize];
+
+        rule.locked || rule.window.is_some()
+    }
+
+    /// Check going from one set of outputs to another
+    pub fn check_outputs(&self, old: PowerControl, new: PowerControl) -> Result<(), PolicyViolation> {
+        for rail in RAILS.iter() {
+            let rule = self.rules[*rail as usize];
+            let was_on = old.contains(rail.flag());
+            let is_on = new.contains(rail.flag());
+
+            if rule.locked && was_on != is_on {
+                return Err(PolicyViolation::Locked(*rail));
+            }
+
+            if rule.critical && was_on && !is_on {
+                return Err(PolicyViolation::Critical(*rail));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check changing a rail's voltage
+    pub fn check_voltage(&self, rail: Rail, old: MilliVolts, new: MilliVolts) -> Result<(), PolicyViolation> {
+        let rule = self.rules[rail as usize];
+
+        if rule.locked && old != new {
+            return Err(PolicyViolation::Locked(rail));
+        }
+
+        match rule.window {
+            Some((min, max)) if new < min || new > max => Err(PolicyViolation::OutOfWindow(rail, new)),
+            _ => Ok(()),
+        }
+    }
+}
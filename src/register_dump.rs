@@ -0,0 +1,274 @@
+//! A snapshot of every register documented in the datasheet, for when you
+//! need to know exactly what state a chip is in. `Axp209::dump_registers()`
+//! takes one and `Axp209::restore_registers()` puts it back.
+//!
+//! `RegisterDump::as_bytes()` hands back the raw snapshot so it can be sent
+//! around as a single blob, and `Display` turns it into something a person
+//! can read:
+//!
+//! ```text
+//! 0x00 Power status              0x31  VBUS_PRESENT | VBUS_USABLE | START_ON_POWER
+//! 0x01 Charging status           0x20  BATTERY_PRESENT
+//! ...
+//! ```
+
+use core::fmt;
+
+use adc_control::AdcControl;
+use charging_status::ChargingStatus;
+use irq::{Irq, IRQ_BANKS};
+use otg_vbus_status::OtgVbusStatus;
+use over_temperature::OverTemperatureControl;
+use power_control::PowerControl;
+use power_status::PowerStatus;
+use timer_control::TimerControl;
+
+/// How a register can be accessed
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Access {
+    /// Only ever read
+    ReadOnly,
+    /// Can be read and written
+    ReadWrite,
+    /// Writing a one to a bit clears it
+    WriteClear,
+}
+
+/// What we know about one register
+#[derive(Debug, Clone, Copy)]
+pub struct RegisterInfo {
+    pub address: u8,
+    pub name: &'static str,
+    pub access: Access,
+}
+
+macro_rules! register {
+    ($address:expr, $name:expr, $access:ident) => {
+        RegisterInfo { address: $address, name: $name, access: Access::$access }
+    };
+}
+
+/// Every register from the datasheet, in address order.
+pub const REGISTERS: &[RegisterInfo] = &[
+    register!(0x00, "Power status", ReadOnly),
+    register!(0x01, "Charging status", ReadOnly),
+    register!(0x02, "OTG VBUS status", ReadOnly),
+    register!(0x04, "Data buffer 0", ReadWrite),
+    register!(0x05, "Data buffer 1", ReadWrite),
+    register!(0x06, "Data buffer 2", ReadWrite),
+    register!(0x07, "Data buffer 3", ReadWrite),
+    register!(0x08, "Data buffer 4", ReadWrite),
+    register!(0x09, "Data buffer 5", ReadWrite),
+    register!(0x0a, "Data buffer 6", ReadWrite),
+    register!(0x0b, "Data buffer 7", ReadWrite),
+    register!(0x0c, "Data buffer 8", ReadWrite),
+    register!(0x0d, "Data buffer 9", ReadWrite),
+    register!(0x0e, "Data buffer 10", ReadWrite),
+    register!(0x0f, "Data buffer 11", ReadWrite),
+    register!(0x12, "Power output control", ReadWrite),
+    register!(0x23, "DCDC2 voltage", ReadWrite),
+    register!(0x25, "DCDC2/LDO3 ramp", ReadWrite),
+    register!(0x27, "DCDC3 voltage", ReadWrite),
+    register!(0x28, "LDO2/LDO4 voltage", ReadWrite),
+    register!(0x29, "LDO3 voltage", ReadWrite),
+    register!(0x30, "VBUS-IPSOUT path", ReadWrite),
+    register!(0x31, "VOFF shutdown voltage", ReadWrite),
+    register!(0x32, "Shutdown/CHGLED control", ReadWrite),
+    register!(0x33, "Charge control 1", ReadWrite),
+    register!(0x34, "Charge control 2", ReadWrite),
+    register!(0x35, "Backup battery charge", ReadWrite),
+    register!(0x36, "PEK parameters", ReadWrite),
+    register!(0x37, "DCDC frequency", ReadWrite),
+    register!(0x38, "VLTF charge", ReadWrite),
+    register!(0x39, "VHTF charge", ReadWrite),
+    register!(0x3a, "APS warning level 1", ReadWrite),
+    register!(0x3b, "APS warning level 2", ReadWrite),
+    register!(0x3c, "VLTF discharge", ReadWrite),
+    register!(0x3d, "VHTF discharge", ReadWrite),
+    register!(0x40, "IRQ enable 1", ReadWrite),
+    register!(0x41, "IRQ enable 2", ReadWrite),
+    register!(0x42, "IRQ enable 3", ReadWrite),
+    register!(0x43, "IRQ enable 4", ReadWrite),
+    register!(0x44, "IRQ enable 5", ReadWrite),
+    register!(0x48, "IRQ status 1", WriteClear),
+    register!(0x49, "IRQ status 2", WriteClear),
+    register!(0x4a, "IRQ status 3", WriteClear),
+    register!(0x4b, "IRQ status 4", WriteClear),
+    register!(0x4c, "IRQ status 5", WriteClear),
+    register!(0x56, "ACIN voltage high", ReadOnly),
+    register!(0x57, "ACIN voltage low", ReadOnly),
+    register!(0x58, "ACIN current high", ReadOnly),
+    register!(0x59, "ACIN current low", ReadOnly),
+    register!(0x5a, "VBUS voltage high", ReadOnly),
+    register!(0x5b, "VBUS voltage low", ReadOnly),
+    register!(0x5c, "VBUS current high", ReadOnly),
+    register!(0x5d, "VBUS current low", ReadOnly),
+    register!(0x5e, "Temperature high", ReadOnly),
+    register!(0x5f, "Temperature low", ReadOnly),
+    register!(0x62, "TS voltage high", ReadOnly),
+    register!(0x63, "TS voltage low", ReadOnly),
+    register!(0x64, "GPIO0 voltage high", ReadOnly),
+    register!(0x65, "GPIO0 voltage low", ReadOnly),
+    register!(0x66, "GPIO1 voltage high", ReadOnly),
+    register!(0x67, "GPIO1 voltage low", ReadOnly),
+    register!(0x70, "Battery power high", ReadOnly),
+    register!(0x71, "Battery power mid", ReadOnly),
+    register!(0x72, "Battery power low", ReadOnly),
+    register!(0x78, "Battery voltage high", ReadOnly),
+    register!(0x79, "Battery voltage low", ReadOnly),
+    register!(0x7a, "Charge current high", ReadOnly),
+    register!(0x7b, "Charge current low", ReadOnly),
+    register!(0x7c, "Discharge current high", ReadOnly),
+    register!(0x7d, "Discharge current low", ReadOnly),
+    register!(0x7e, "IPSOUT voltage high", ReadOnly),
+    register!(0x7f, "IPSOUT voltage low", ReadOnly),
+    register!(0x80, "DCDC mode", ReadWrite),
+    register!(0x82, "ADC enable 1", ReadWrite),
+    register!(0x83, "ADC enable 2", ReadWrite),
+    register!(0x84, "ADC sample rate/TS", ReadWrite),
+    register!(0x85, "GPIO ADC input range", ReadWrite),
+    register!(0x86, "GPIO1 ADC IRQ rising", ReadWrite),
+    register!(0x87, "GPIO1 ADC IRQ falling", ReadWrite),
+    register!(0x8a, "Timer control", ReadWrite),
+    register!(0x8b, "VBUS monitoring", ReadWrite),
+    register!(0x8f, "Over-temperature control", ReadWrite),
+    register!(0x90, "GPIO0 control", ReadWrite),
+    register!(0x91, "GPIO0 LDO voltage", ReadWrite),
+    register!(0x92, "GPIO1 control", ReadWrite),
+    register!(0x93, "GPIO2 control", ReadWrite),
+    register!(0x94, "GPIO status", ReadOnly),
+    register!(0x95, "GPIO3 control", ReadWrite),
+    register!(0xb0, "Coulomb charge 3", ReadOnly),
+    register!(0xb1, "Coulomb charge 2", ReadOnly),
+    register!(0xb2, "Coulomb charge 1", ReadOnly),
+    register!(0xb3, "Coulomb charge 0", ReadOnly),
+    register!(0xb4, "Coulomb discharge 3", ReadOnly),
+    register!(0xb5, "Coulomb discharge 2", ReadOnly),
+    register!(0xb6, "Coulomb discharge 1", ReadOnly),
+    register!(0xb7, "Coulomb discharge 0", ReadOnly),
+    register!(0xb8, "Coulomb control", ReadWrite),
+    register!(0xb9, "Battery level", ReadOnly),
+];
+
+/// The order writable registers are restored in. Voltages go in before the
+/// outputs get switched on, and the outputs go last of all so the system
+/// doesn't get yanked out from under us halfway through.
+pub const RESTORE_ORDER: &[u8] = &[
+    // Data buffer
+    0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f,
+    // Input path, shutdown and charging
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36,
+    0x38, 0x39, 0x3a, 0x3b, 0x3c, 0x3d,
+    // Output voltages
+    0x37, 0x80, 0x25, 0x23, 0x27, 0x28, 0x29,
+    // ADC, timer, GPIOs and everything else
+    0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x8a, 0x8b, 0x8f,
+    0x90, 0x91, 0x92, 0x93, 0x95, 0xb8,
+    // IRQs
+    0x40, 0x41, 0x42, 0x43, 0x44,
+    // Outputs
+    0x12,
+];
+
+/// Bits that must never be written back, because writing them does
+/// something rather than set something.
+pub fn restore_mask(address: u8) -> u8 {
+    match address {
+        // Shut the chip down
+        0x32 => 0b1000_0000,
+        // Clear the expired flag
+        0x8a => 0b1000_0000,
+        // Clear the coulomb counters
+        0xb8 => 0b0010_0000,
+        _ => 0,
+    }
+}
+
+/// A copy of every register on the chip. Only the ones listed in
+/// `REGISTERS` are actually read, the rest are left at zero.
+#[derive(Clone)]
+pub struct RegisterDump {
+    values: [u8; 256],
+}
+
+impl RegisterDump {
+    /// Rebuild a snapshot from a blob made by `as_bytes()`
+    pub fn new(values: [u8; 256]) -> Self {
+        RegisterDump {
+            values,
+        }
+    }
+
+    /// The raw snapshot, indexed by register address
+    pub fn as_bytes(&self) -> &[u8; 256] {
+        &self.values
+    }
+
+    /// The value read from a register
+    pub fn get(&self, address: u8) -> u8 {
+        self.values[address as usize]
+    }
+
+    pub fn set(&mut self, address: u8, value: u8) {
+        self.values[address as usize] = value;
+    }
+
+    /// The value that should be written back to a register when restoring
+    pub fn restore_value(&self, address: u8) -> u8 {
+        self.get(address) & !restore_mask(address)
+    }
+
+    fn irq(&self, first: u8) -> Irq {
+        let mut banks = [0u8; IRQ_BANKS];
+        banks.copy_from_slice(&self.values[first as usize..first as usize + IRQ_BANKS]);
+
+        Irq::from_banks(&banks)
+    }
+
+    /// Write out whatever the crate knows about a register's value
+    fn decode(&self, f: &mut fmt::Formatter, address: u8) -> fmt::Result {
+        let value = self.get(address);
+
+        match address {
+            0x00 => write!(f, "{:?}", PowerStatus::new(value)),
+            0x01 => write!(f, "{:?}", ChargingStatus::new(value)),
+            0x02 => write!(f, "{:?}", OtgVbusStatus::new(value)),
+            0x12 => write!(f, "{:?}", PowerControl::new(value)),
+            0x40 => write!(f, "{:?}", self.irq(0x40)),
+            0x48 => write!(f, "{:?}", self.irq(0x48)),
+            0x82 => write!(f, "{:?}", AdcControl::new((value as u16) << 8 | self.get(0x83) as u16)),
+            0x8a => {
+                let timer = TimerControl::new(value);
+                write!(f, "expired: {}, minutes: {}", timer.expired(), timer.minutes())
+            },
+            0x8f => write!(f, "{:?}", OverTemperatureControl::new(value)),
+            0xb9 => write!(f, "{}%", value & 0x7f),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for RegisterDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for register in REGISTERS {
+            write!(f, "0x{:02x} {:<25} 0x{:02x}  ", register.address, register.name,
+                   self.get(register.address))?;
+            self.decode(f, register.address)?;
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RegisterDump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut list = f.debug_map();
+
+        for register in REGISTERS {
+            list.entry(&register.name, &format_args!("0x{:02x}", self.get(register.address)));
+        }
+
+        list.finish()
+    }
+}
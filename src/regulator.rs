@@ -0,0 +1,226 @@
+//! Sharing rails between parts of a program that don't know about each
+//! other, the same way the Linux regulator framework does it. Each part
+//! asks for its own `Regulator` for a rail and switches it on and off as it
+//! pleases. The rail only actually goes off once every consumer has
+//! disabled it:
+//!
+//! ```ignore
+//! let wifi = pmic.regulator(Rail::Ldo3)?;
+//! let sensor = pmic.regulator(Rail::Ldo3)?;
+//!
+//! wifi.enable(&mut pmic)?;
+//! sensor.enable(&mut pmic)?;
+//! wifi.disable(&mut pmic)?;   // Still on, the sensor needs it
+//! sensor.release(&mut pmic)?; // Now it's off
+//! ```
+//!
+//! Consumers can also ask for a voltage window. The rail gets set to the
+//! lowest voltage that's inside every window, or if there isn't one the
+//! request is refused with `Error::VoltageConflict`.
+//!
+//! Enabling is counted per consumer like `regulator_enable()` in Linux, so a
+//! consumer that enabled the rail twice has to disable it twice too.
+//!
+//! A `Regulator` can't be copied, and everything it does goes through the
+//! `Axp209` it's handed, which keeps the book-keeping. Nothing stops it being
+//! handed a different `Axp209` than the one it came from, so don't. There's
+//! room for `MAX_CONSUMERS` per rail, and a `Regulator` dropped without
+//! `release()` keeps its slot (and anything it had enabled) until the
+//! `Axp209` goes away.
+//!
+//! The book-keeping only knows about what goes through a `Regulator`. Calling
+//! `Axp209::set_rail_enabled()` or `Axp209::set_power_control()` on a shared
+//! rail switches it regardless of who is using it, and the next consumer to
+//! enable or disable it won't notice.
+
+use hal::blocking::i2c::{Read, Write, WriteRead};
+
+use error::Error;
+use rail::Rail;
+use units::MilliVolts;
+use Axp209;
+
+/// How many consumers a rail can have at once
+pub const MAX_CONSUMERS: usize = 8;
+
+/// Who is using one rail, and what they want from it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RailUsers {
+    /// A bit per consumer slot that's handed out
+    taken: u8,
+    /// How many times each consumer has enabled the rail without disabling
+    /// it again
+    enabled: [u8; MAX_CONSUMERS],
+    /// Each consumer's voltage window, inclusive
+    requests: [Option<(MilliVolts, MilliVolts)>; MAX_CONSUMERS],
+}
+
+impl RailUsers {
+    /// Hand out a free consumer slot
+    pub fn take(&mut self) -> Option<u8> {
+        let slot = (!self.taken).trailing_zeros();
+
+        if slot as usize >= MAX_CONSUMERS {
+            return None;
+        }
+
+        self.taken |= 1 << slot;
+        Some(slot as u8)
+    }
+
+    /// How many consumers want the rail on
+    pub fn enable_count(&self) -> u32 {
+        self.enabled.iter().filter(|x| **x > 0).count() as u32
+    }
+
+    /// The window every consumer's request fits in, or `None` if they
+    /// don't agree. No requests at all allows anything.
+    pub fn window(&self) -> Option<(MilliVolts, MilliVolts)> {
+        let mut min = MilliVolts(0);
+        let mut max = MilliVolts(u16::MAX);
+
+        for (a, b) in self.requests.iter().flatten() {
+            min = min.max(*a);
+            max = max.min(*b);
+        }
+
+        if min <= max {
+            Some((min, max))
+        } else {
+            None
+        }
+    }
+}
+
+/// One consumer's hold on a rail. Get one with `Axp209::regulator()`.
+#[derive(Debug)]
+pub struct Regulator {
+    rail: Rail,
+    consumer: u8,
+}
+
+impl Regulator {
+    pub(crate) fn new(rail: Rail, consumer: u8) -> Self {
+        Regulator {
+            rail,
+            consumer,
+        }
+    }
+
+    pub fn rail(&self) -> Rail {
+        self.rail
+    }
+
+    fn bit(&self) -> u8 {
+        1 << self.consumer
+    }
+
+    /// Whether this consumer wants the rail on. It could still be on
+    /// because of someone else.
+    pub fn is_enabled<I2C>(&self, pmic: &Axp209<I2C>) -> bool {
+        self.enable_count(pmic) > 0
+    }
+
+    /// How many times this consumer has enabled the rail without disabling
+    /// it again
+    pub fn enable_count<I2C>(&self, pmic: &Axp209<I2C>) -> u8 {
+        pmic.rail_users[self.rail as usize].enabled[self.consumer as usize]
+    }
+
+    /// Ask for the rail to be on. Switches it on if nobody else had. Every
+    /// `enable()` needs a `disable()` to undo it.
+    pub fn enable<I2C, E>(&self, pmic: &mut Axp209<I2C>) -> Result<(), Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+    {
+        let users = &mut pmic.rail_users[self.rail as usize];
+        let first = users.enable_count() == 0;
+        let count = &mut users.enabled[self.consumer as usize];
+        *count = count.saturating_add(1);
+
+        if first {
+            if let Err(e) = pmic.set_rail_enabled(self.rail, true) {
+                pmic.rail_users[self.rail as usize].enabled[self.consumer as usize] -= 1;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Undo one `enable()`. Switches the rail off if that was the last one
+    /// and nobody else wants it on. Does nothing if this consumer hasn't
+    /// enabled it.
+    pub fn disable<I2C, E>(&self, pmic: &mut Axp209<I2C>) -> Result<(), Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+    {
+        let users = &mut pmic.rail_users[self.rail as usize];
+        let count = &mut users.enabled[self.consumer as usize];
+
+        if *count == 0 {
+            return Ok(());
+        }
+
+        *count -= 1;
+
+        if users.enable_count() == 0 {
+            if let Err(e) = pmic.set_rail_enabled(self.rail, false) {
+                pmic.rail_users[self.rail as usize].enabled[self.consumer as usize] += 1;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ask for the rail's voltage to be somewhere between `min` and `max`.
+    /// The rail is set to the lowest voltage that keeps every consumer
+    /// happy. `Error::NoVoltage` for EXTEN, which doesn't have a voltage.
+    pub fn set_voltage<I2C, E>(&self, pmic: &mut Axp209<I2C>, min: MilliVolts, max: MilliVolts) -> Result<(), Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+    {
+        let field = self.rail.voltage_field().ok_or(Error::NoVoltage(self.rail))?;
+        let users = &mut pmic.rail_users[self.rail as usize];
+        let old = users.requests[self.consumer as usize];
+        users.requests[self.consumer as usize] = Some((min, max));
+
+        // The lowest voltage the chip can do that fits every window
+        let point = users.window().and_then(|(min, max)| {
+            field.round_up(min).filter(|x| *x <= max)
+        });
+
+        let result = match point {
+            Some(x) => pmic.set_rail_voltage(self.rail, x),
+            None => Err(Error::VoltageConflict(self.rail)),
+        };
+
+        if result.is_err() {
+            pmic.rail_users[self.rail as usize].requests[self.consumer as usize] = old;
+        }
+
+        result
+    }
+
+    /// Give up on the rail altogether, however many times it was enabled,
+    /// switching it off if this was the last consumer that wanted it on. The
+    /// voltage is left where it is. The consumer is gone even if switching
+    /// off fails, in which case the rail is left on.
+    pub fn release<I2C, E>(self, pmic: &mut Axp209<I2C>) -> Result<(), Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+    {
+        let users = &mut pmic.rail_users[self.rail as usize];
+        let was_enabled = users.enabled[self.consumer as usize] > 0;
+        users.requests[self.consumer as usize] = None;
+        users.enabled[self.consumer as usize] = 0;
+        users.taken &= !self.bit();
+
+        if was_enabled && users.enable_count() == 0 {
+            pmic.set_rail_enabled(self.rail, false)
+        } else {
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Try again when the bus has a bad moment. Some boards share the PMIC's
+//! bus with other, less well behaved chips, and the odd NAK is a fact of
+//! life. `Retry` wraps the bus and has another go at anything that fails,
+//! waiting a little longer each time:
+//!
+//! ```ignore
+//! let policy = RetryPolicy::new(3, 100).retry_if(|e| *e != MyError::Disconnected);
+//! let mut pmic = Axp209::new(Retry::new(i2c, delay, policy));
+//! ```
+//!
+//! Every register on the chip can be safely read twice, and writing the
+//! same value twice does no harm to most of them, so nearly everything gets
+//! retried. The exception is a write that sets a bit which does something
+//! rather than sets something: shutting down (0x32 bit 7), restarting the
+//! timer (0x8a bit 7) or clearing the coulomb counters (0xb8 bit 5). A write
+//! that failed might have got through anyway, and doing it again would do
+//! it twice, so those writes only get the one go (see
+//! `register_dump::restore_mask()`). Register writes that matter a lot are
+//! also checked by reading them back (see `Axp209::set_power_control()`),
+//! which catches the case where a write got through but then failed anyway.
+//!
+//! The waiting is done with embedded-hal 0.1's `DelayUs<u32>`, as that's the
+//! version this crate is built on. There's no `DelayNs` until 1.0.
+
+use hal::blocking::delay::DelayUs;
+use hal::blocking::i2c::{Read, Write, WriteRead};
+
+use register_dump::restore_mask;
+
+/// How hard to try
+#[derive(Clone, Copy)]
+pub struct RetryPolicy<E> {
+    /// How many goes in total, including the first
+    attempts: u8,
+    /// How long to wait before the first retry, in microseconds. It doubles
+    /// every retry after that.
+    backoff: u32,
+    /// Whether an error is worth trying again for
+    retryable: fn(&E) -> bool,
+}
+
+impl<E> RetryPolicy<E> {
+    /// Retry every error until there have been `attempts` goes in total,
+    /// waiting `backoff` microseconds before the first retry. There's always
+    /// at least one go, so zero attempts is the same as one.
+    pub fn new(attempts: u8, backoff: u32) -> Self {
+        RetryPolicy {
+            attempts: attempts.max(1),
+            backoff,
+            retryable: |_| true,
+        }
+    }
+
+    /// Only retry errors that `retryable` says yes to. Anything else gets
+    /// handed back straight away.
+    pub fn retry_if(mut self, retryable: fn(&E) -> bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
+
+    pub fn attempts(&self) -> u8 {
+        self.attempts
+    }
+
+    pub fn backoff(&self) -> u32 {
+        self.backoff
+    }
+}
+
+pub struct Retry<I2C, D, E> {
+    bus: I2C,
+    delay: D,
+    policy: RetryPolicy<E>,
+}
+
+impl<I2C, D, E> Retry<I2C, D, E>
+where
+    D: DelayUs<u32>,
+{
+    pub fn new(bus: I2C, delay: D, policy: RetryPolicy<E>) -> Self {
+        Retry {
+            bus,
+            delay,
+            policy,
+        }
+    }
+
+    pub fn policy(&self) -> &RetryPolicy<E> {
+        &self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: RetryPolicy<E>) {
+        self.policy = policy;
+    }
+
+    pub fn bus_mut(&mut self) -> &mut I2C {
+        &mut self.bus
+    }
+
+    pub fn delay_mut(&mut self) -> &mut D {
+        &mut self.delay
+    }
+
+    pub fn into_inner(self) -> (I2C, D) {
+        (self.bus, self.delay)
+    }
+
+    /// Keep running `f` until it works, the error isn't worth retrying, or
+    /// we've run out of attempts
+    fn run<T, F>(&mut self, mut f: F) -> Result<T, E>
+    where
+        F: FnMut(&mut I2C) -> Result<T, E>,
+    {
+        let mut wait = self.policy.backoff;
+        let mut attempt = 1;
+
+        loop {
+            match f(&mut self.bus) {
+                Err(ref e) if attempt < self.policy.attempts && (self.policy.retryable)(e) => {
+                    self.delay.delay_us(wait);
+                    wait = wait.saturating_mul(2);
+                    attempt += 1;
+                },
+                x => return x,
+            }
+        }
+    }
+}
+
+/// Whether a register write sets a bit that does something every time it's
+/// written. The first byte is the register, and the rest go to it and the
+/// registers after it.
+fn is_one_shot(bytes: &[u8]) -> bool {
+    let start = match bytes.first() {
+        Some(x) => *x,
+        None => return false,
+    };
+
+    bytes[1..].iter()
+        .enumerate()
+        .any(|(i, x)| restore_mask(start.wrapping_add(i as u8)) & x != 0)
+}
+
+impl<I2C, D, E> Write for Retry<I2C, D, E>
+where
+    I2C: Write<Error = E>,
+    D: DelayUs<u32>,
+{
+    type Error = E;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        if is_one_shot(bytes) {
+            return self.bus.write(address, bytes);
+        }
+
+        self.run(|bus| bus.write(address, bytes))
+    }
+}
+
+impl<I2C, D, E> Read for Retry<I2C, D, E>
+where
+    I2C: Read<Error = E>,
+    D: DelayUs<u32>,
+{
+    type Error = E;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.run(|bus| bus.read(address, buffer))
+    }
+}
+
+impl<I2C, D, E> WriteRead for Retry<I2C, D, E>
+where
+    I2C: WriteRead<Error = E>,
+    D: DelayUs<u32>,
+{
+    type Error = E;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.run(|bus| bus.write_read(address, bytes, buffer))
+    }
+}
@@ -0,0 +1,210 @@
+//! Bringing rails up in a set order, and taking them down in the reverse,
+//! with a pause between each one for things to settle. The steps are plain
+//! data, so they can live in a `const`:
+//!
+//! ```ignore
+//! const BRING_UP: &[Step] = &[
+//!     Step::rail(Rail::Dcdc2).voltage(MilliVolts(1200)).wait(5),
+//!     Step::rail(Rail::Ldo3).voltage(MilliVolts(3300)).wait(5),
+//!     Step::rail(Rail::Exten).wait(5),
+//! ];
+//!
+//! PowerSequence::new(BRING_UP).power_up(&mut pmic, &mut delay)?;
+//! ```
+//!
+//! Every step is read back before moving on. That only shows the chip took
+//! the setting, not that the rail actually came up: `power_status()` is
+//! about the inputs and the battery, and the AXP209 has no power good flag
+//! for its outputs, so there's nothing better to check. If one fails, the steps that
+//! already ran are undone in reverse, putting rails back how they were
+//! before the sequence started, and you get a `SequenceError` saying which
+//! step it was and whether undoing worked.
+//!
+//! Everything goes through the usual setters, so the rail policy still
+//! applies.
+
+use hal::blocking::delay::DelayMs;
+use hal::blocking::i2c::{Read, Write, WriteRead};
+
+use error::Error;
+use power_control::PowerControl;
+use rail::{Rail, RAILS, RAIL_COUNT};
+use units::MilliVolts;
+use Axp209;
+
+/// One rail in a sequence
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Step {
+    pub rail: Rail,
+    /// What to set the voltage to before switching on, if anything
+    pub voltage: Option<MilliVolts>,
+    /// How long to wait afterwards, in milliseconds
+    pub delay: u16,
+}
+
+impl Step {
+    pub const fn rail(rail: Rail) -> Self {
+        Step {
+            rail,
+            voltage: None,
+            delay: 0,
+        }
+    }
+
+    pub const fn voltage(mut self, value: MilliVolts) -> Self {
+        self.voltage = Some(value);
+        self
+    }
+
+    pub const fn wait(mut self, ms: u16) -> Self {
+        self.delay = ms;
+        self
+    }
+}
+
+/// What went wrong running a sequence
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct SequenceError<E> {
+    /// Which step failed, counting from the start of the list for both
+    /// directions
+    pub step: usize,
+    pub error: Error<E>,
+    /// Whether the steps already taken were undone. If not, the rails are
+    /// in some state in between.
+    pub rolled_back: bool,
+}
+
+/// How the rails in a sequence were before it started
+struct Saved {
+    outputs: PowerControl,
+    voltages: [Option<MilliVolts>; RAIL_COUNT],
+}
+
+pub struct PowerSequence<'a> {
+    steps: &'a [Step],
+}
+
+impl<'a> PowerSequence<'a> {
+    pub fn new(steps: &'a [Step]) -> Self {
+        PowerSequence {
+            steps,
+        }
+    }
+
+    pub fn steps(&self) -> &'a [Step] {
+        self.steps
+    }
+
+    /// Run the steps in order, setting voltages and switching rails on
+    pub fn power_up<I2C, E, D>(&self, pmic: &mut Axp209<I2C>, delay: &mut D) -> Result<(), SequenceError<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+        D: DelayMs<u16>,
+    {
+        let saved = self.save(pmic)?;
+
+        for (i, step) in self.steps.iter().enumerate() {
+            if let Err(error) = power_up_step(pmic, step) {
+                let rolled_back = self.restore(pmic, delay, &saved, (0..=i).rev()).is_ok();
+                return Err(SequenceError { step: i, error, rolled_back });
+            }
+
+            delay.delay_ms(step.delay);
+        }
+
+        Ok(())
+    }
+
+    /// Run the steps backwards, switching rails off. Voltages are left
+    /// alone.
+    pub fn power_down<I2C, E, D>(&self, pmic: &mut Axp209<I2C>, delay: &mut D) -> Result<(), SequenceError<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+        D: DelayMs<u16>,
+    {
+        let saved = self.save(pmic)?;
+
+        for (i, step) in self.steps.iter().enumerate().rev() {
+            if let Err(error) = pmic.set_rail_enabled(step.rail, false) {
+                let rolled_back = self.restore(pmic, delay, &saved, i..self.steps.len()).is_ok();
+                return Err(SequenceError { step: i, error, rolled_back });
+            }
+
+            delay.delay_ms(step.delay);
+        }
+
+        Ok(())
+    }
+
+    /// Remember the outputs and the voltages of every rail that gets one set
+    fn save<I2C, E>(&self, pmic: &mut Axp209<I2C>) -> Result<Saved, SequenceError<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+    {
+        // Nothing has happened yet, so there's nothing to undo
+        let failed = |error| SequenceError { step: 0, error, rolled_back: true };
+        let mut voltages = [None; RAIL_COUNT];
+
+        for rail in RAILS.iter() {
+            if self.steps.iter().any(|x| x.rail == *rail && x.voltage.is_some()) {
+                voltages[*rail as usize] = Some(pmic.rail_voltage(*rail).map_err(failed)?);
+            }
+        }
+
+        Ok(Saved {
+            outputs: pmic.power_control().map_err(failed)?,
+            voltages,
+        })
+    }
+
+    /// Put the rails of the given steps back how they were, in that order.
+    /// Carries on past failures to put back as much as it can.
+    fn restore<I2C, E, D, S>(&self, pmic: &mut Axp209<I2C>, delay: &mut D, saved: &Saved, steps: S) -> Result<(), Error<E>>
+    where
+        I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+        D: DelayMs<u16>,
+        S: Iterator<Item = usize>,
+    {
+        let mut result = Ok(());
+
+        for i in steps {
+            let step = &self.steps[i];
+            let on = saved.outputs.contains(step.rail.flag());
+
+            if let Err(e) = pmic.set_rail_enabled(step.rail, on) {
+                result = Err(e);
+            }
+
+            if let Some(voltage) = saved.voltages[step.rail as usize] {
+                if let Err(e) = pmic.set_rail_voltage(step.rail, voltage) {
+                    result = Err(e);
+                }
+            }
+
+            delay.delay_ms(step.delay);
+        }
+
+        result
+    }
+}
+
+/// Set the voltage, check it took, then switch the rail on. Switching is
+/// always checked by `set_power_control()`.
+fn power_up_step<I2C, E>(pmic: &mut Axp209<I2C>, step: &Step) -> Result<(), Error<E>>
+where
+    I2C: WriteRead<Error = E> + Write<Error = E> + Read<Error = E>,
+{
+    if let Some(voltage) = step.voltage {
+        let field = step.rail.voltage_field().ok_or(Error::NoVoltage(step.rail))?;
+        let wrote = field.encode(voltage).ok_or(Error::VoltageOutOfRange(step.rail, voltage))?;
+
+        pmic.set_rail_voltage(step.rail, voltage)?;
+
+        let read = pmic.get_8bit_register(field.register)?;
+        if read & field.mask != wrote {
+            return Err(Error::VerifyFailed { register: field.register, wrote, read });
+        }
+    }
+
+    pmic.set_rail_enabled(step.rail, true)
+}
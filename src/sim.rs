@@ -0,0 +1,219 @@
+//! An AXP209 that only exists in memory, for testing without a board on the
+//! desk. `Sim` implements the same I2C traits as a real bus, so it can be
+//! handed straight to `Axp209::new()`:
+//!
+//! ```ignore
+//! let mut sim = Sim::new();
+//! sim.set_adc(Channel::BatteryVoltage, 2800);
+//!
+//! let mut pmic = Axp209::new(sim);
+//! let voltage = pmic.battery_voltage()?;
+//! ```
+//!
+//! It behaves like the chip as far as the driver can tell: read-only
+//! registers ignore writes, IRQ status bits get cleared by writing a one to
+//! them, and reads and writes carry on into the next register. What it
+//! doesn't do is any actual power management. The ADC registers, status
+//! registers and so on hold whatever was last put in them with the
+//! `set_*` methods here, and nothing changes by itself.
+//!
+//! Only built with the `sim` feature (and for the crate's own tests).
+
+use hal::blocking::i2c::{Read, Write, WriteRead};
+
+use channel::Channel;
+use coulomb_counter::CoulombControl;
+use irq::Irq;
+use register_dump::{Access, REGISTERS};
+use timer_control::TimerControl;
+use {Registers, ADDRESS};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum SimError {
+    /// Something other than the AXP209 was addressed
+    WrongAddress(u8),
+    /// A write with no register address in it
+    EmptyWrite,
+}
+
+/// Which bits of a register can be written, and which get cleared by
+/// writing a one to them. Undocumented registers can't be written at all.
+fn write_masks(address: u8) -> (u8, u8) {
+    match address {
+        // The expired flag is write-1-to-clear, the minutes are normal
+        0x8a => (0x7f, 0x80),
+        _ => match REGISTERS.iter().find(|x| x.address == address) {
+            Some(info) => match info.access {
+                Access::ReadOnly => (0x00, 0x00),
+                Access::ReadWrite => (0xff, 0x00),
+                Access::WriteClear => (0x00, 0xff),
+            },
+            None => (0x00, 0x00),
+        },
+    }
+}
+
+pub struct Sim {
+    registers: [u8; 256],
+    /// The register the next read or write goes to
+    pointer: u8,
+    /// Set once the shutdown bit gets written
+    shut_down: bool,
+    /// The last register written through the bus
+    last_written: Option<u8>,
+}
+
+impl Default for Sim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sim {
+    /// A chip with the power-on defaults from the datasheet, with nothing
+    /// plugged in and no battery.
+    pub fn new() -> Self {
+        let mut registers = [0u8; 256];
+
+        registers[Registers::PowerControl as usize] = 0x5f;
+        registers[Registers::AdcControl as usize] = 0x83;
+        registers[Registers::AdcControl as usize + 1] = 0x80;
+        registers[Registers::AdcSampleTs as usize] = 0x83;
+        registers[0x33] = 0xc8;
+        registers[Registers::IrqEnable as usize] = 0xd8;
+        registers[Registers::IrqEnable as usize + 1] = 0xff;
+        registers[Registers::IrqEnable as usize + 2] = 0x3b;
+        registers[Registers::IrqEnable as usize + 3] = 0xc1;
+        registers[Registers::BatteryLevel as usize] = 0x7f;
+
+        Sim {
+            registers,
+            pointer: 0,
+            shut_down: false,
+            last_written: None,
+        }
+    }
+
+    /// Look at a register without going through the bus
+    pub fn register(&self, address: u8) -> u8 {
+        self.registers[address as usize]
+    }
+
+    /// Set a register without going through the bus. Anything goes here,
+    /// read-only or not, so this is how the status registers get filled in.
+    pub fn set_register(&mut self, address: u8, value: u8) {
+        self.registers[address as usize] = value;
+    }
+
+    /// Put a raw value into an ADC channel's registers
+    pub fn set_adc(&mut self, channel: Channel, raw: u32) {
+        let info = channel.info();
+        let start = info.register as usize;
+
+        info.encode(raw, &mut self.registers[start..start + info.register_count()]);
+    }
+
+    /// Make IRQs fire. They stay set until cleared like on the real chip,
+    /// and are set whether they're enabled or not.
+    pub fn raise_irq(&mut self, irq: Irq) {
+        let start = Registers::IrqStatus as usize;
+
+        for (i, bank) in irq.to_banks().iter().enumerate() {
+            self.registers[start + i] |= *bank;
+        }
+    }
+
+    /// Let the wakeup timer run out
+    pub fn expire_timer(&mut self) {
+        self.registers[Registers::TimerControl as usize] |= TimerControl::TIMER_EXPIRED.bits();
+    }
+
+    /// Whether the driver told the chip to shut down
+    pub fn is_shut_down(&self) -> bool {
+        self.shut_down
+    }
+
+    /// The last register written through the bus, for checking the order
+    /// things happen in
+    pub fn last_written(&self) -> Option<u8> {
+        self.last_written
+    }
+
+    fn check_address(address: u8) -> Result<(), SimError> {
+        if address == ADDRESS {
+            Ok(())
+        } else {
+            Err(SimError::WrongAddress(address))
+        }
+    }
+
+    fn write_register(&mut self, address: u8, value: u8) {
+        let (writable, clearable) = write_masks(address);
+        let old = self.registers[address as usize];
+        self.last_written = Some(address);
+
+        self.registers[address as usize] = (old & !writable & !(value & clearable))
+            | (value & writable);
+
+        match address {
+            // Shutting down is a one-shot, the bit doesn't stick
+            0x32 if value & 0x80 != 0 => {
+                self.shut_down = true;
+                self.registers[0x32] &= 0x7f;
+            },
+            // Same for clearing the coulomb counters
+            0xb8 if value & CoulombControl::CLEAR.bits() != 0 => {
+                let start = Registers::CoulombCounter as usize;
+
+                for x in &mut self.registers[start..start + 8] {
+                    *x = 0;
+                }
+                self.registers[0xb8] &= !CoulombControl::CLEAR.bits();
+            },
+            _ => {},
+        }
+    }
+}
+
+impl Write for Sim {
+    type Error = SimError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        Self::check_address(address)?;
+
+        let (first, values) = bytes.split_first().ok_or(SimError::EmptyWrite)?;
+        self.pointer = *first;
+
+        for value in values {
+            let register = self.pointer;
+            self.write_register(register, *value);
+            self.pointer = self.pointer.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for Sim {
+    type Error = SimError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        Self::check_address(address)?;
+
+        for x in buffer.iter_mut() {
+            *x = self.registers[self.pointer as usize];
+            self.pointer = self.pointer.wrapping_add(1);
+        }
+
+        Ok(())
+    }
+}
+
+impl WriteRead for Sim {
+    type Error = SimError;
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.write(address, bytes)?;
+        self.read(address, buffer)
+    }
+}
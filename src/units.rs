@@ -0,0 +1,227 @@
+//! Small wrappers around plain integers so a voltage can't be mistaken for a
+//! current. They add, subtract and compare like the numbers they hold, and
+//! print themselves with their unit attached:
+//!
+//! ```ignore
+//!     let voltage = pmic.battery_voltage().unwrap();
+//!     println!("Voltage: {}", voltage);   // Voltage: 4184mV
+//!
+//!     let raw: u16 = voltage.0;
+//! ```
+//!
+//! With the `uom` feature turned on they also convert into the matching
+//! `uom` quantities.
+
+use core::fmt;
+use core::ops::{Add, AddAssign, Div, Mul, Sub, SubAssign};
+
+macro_rules! unit {
+    ($(#[$doc:meta])* $name:ident($inner:ty), $suffix:expr) => {
+        $(#[$doc])*
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default, Hash)]
+        pub struct $name(pub $inner);
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                $name(self.0 + other.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                self.0 += other.0;
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                $name(self.0 - other.0)
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                self.0 -= other.0;
+            }
+        }
+
+        impl Mul<$inner> for $name {
+            type Output = Self;
+
+            fn mul(self, other: $inner) -> Self {
+                $name(self.0 * other)
+            }
+        }
+
+        impl Div<$inner> for $name {
+            type Output = Self;
+
+            fn div(self, other: $inner) -> Self {
+                $name(self.0 / other)
+            }
+        }
+
+        impl From<$inner> for $name {
+            fn from(value: $inner) -> Self {
+                $name(value)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}{}", self.0, $suffix)
+            }
+        }
+    };
+}
+
+unit!(
+    /// Electric potential in millivolts
+    MilliVolts(u16), "mV");
+unit!(
+    /// Electric current in milliamps
+    MilliAmps(u16), "mA");
+unit!(
+    /// Power in microwatts
+    MicroWatts(u32), "µW");
+unit!(
+    /// Electric charge in milliamp hours
+    MilliAmpHours(u16), "mAh");
+unit!(
+    /// Electric potential in microvolts
+    MicroVolts(u32), "µV");
+unit!(
+    /// Electric current in microamps
+    MicroAmps(u32), "µA");
+
+/// Rounds to the nearest millivolt
+impl From<MicroVolts> for MilliVolts {
+    fn from(value: MicroVolts) -> Self {
+        MilliVolts(((value.0 + 500) / 1000) as u16)
+    }
+}
+
+/// Rounds to the nearest milliamp
+impl From<MicroAmps> for MilliAmps {
+    fn from(value: MicroAmps) -> Self {
+        MilliAmps(((value.0 + 500) / 1000) as u16)
+    }
+}
+
+/// Temperature in tenths of a degree celsius
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Default, Hash)]
+pub struct DeciCelsius(pub i16);
+
+impl DeciCelsius {
+    /// Whole degrees, rounded towards zero
+    pub fn celsius(&self) -> i16 {
+        self.0 / 10
+    }
+}
+
+impl Add for DeciCelsius {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        DeciCelsius(self.0 + other.0)
+    }
+}
+
+impl Sub for DeciCelsius {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        DeciCelsius(self.0 - other.0)
+    }
+}
+
+impl Mul<i16> for DeciCelsius {
+    type Output = Self;
+
+    fn mul(self, other: i16) -> Self {
+        DeciCelsius(self.0 * other)
+    }
+}
+
+impl From<i16> for DeciCelsius {
+    fn from(value: i16) -> Self {
+        DeciCelsius(value)
+    }
+}
+
+impl fmt::Display for DeciCelsius {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let value = (self.0 as i32).abs();
+
+        write!(f, "{}{}.{}°C", sign, value / 10, value % 10)
+    }
+}
+
+/// Volts times amps is watts, and milli times milli is micro
+impl Mul<MilliAmps> for MilliVolts {
+    type Output = MicroWatts;
+
+    fn mul(self, other: MilliAmps) -> MicroWatts {
+        MicroWatts(self.0 as u32 * other.0 as u32)
+    }
+}
+
+impl Mul<MilliVolts> for MilliAmps {
+    type Output = MicroWatts;
+
+    fn mul(self, other: MilliVolts) -> MicroWatts {
+        other * self
+    }
+}
+
+#[cfg(feature = "uom")]
+mod uom_conversions {
+    use super::*;
+
+    use uom::si::f32::{ElectricCurrent, ElectricPotential, Power, ThermodynamicTemperature};
+    use uom::si::electric_current::{microampere, milliampere};
+    use uom::si::electric_potential::{microvolt, millivolt};
+    use uom::si::power::microwatt;
+    use uom::si::thermodynamic_temperature::degree_celsius;
+
+    impl From<MilliVolts> for ElectricPotential {
+        fn from(value: MilliVolts) -> Self {
+            ElectricPotential::new::<millivolt>(value.0 as f32)
+        }
+    }
+
+    impl From<MilliAmps> for ElectricCurrent {
+        fn from(value: MilliAmps) -> Self {
+            ElectricCurrent::new::<milliampere>(value.0 as f32)
+        }
+    }
+
+    impl From<MicroVolts> for ElectricPotential {
+        fn from(value: MicroVolts) -> Self {
+            ElectricPotential::new::<microvolt>(value.0 as f32)
+        }
+    }
+
+    impl From<MicroAmps> for ElectricCurrent {
+        fn from(value: MicroAmps) -> Self {
+            ElectricCurrent::new::<microampere>(value.0 as f32)
+        }
+    }
+
+    impl From<MicroWatts> for Power {
+        fn from(value: MicroWatts) -> Self {
+            Power::new::<microwatt>(value.0 as f32)
+        }
+    }
+
+    impl From<DeciCelsius> for ThermodynamicTemperature {
+        fn from(value: DeciCelsius) -> Self {
+            ThermodynamicTemperature::new::<degree_celsius>(value.0 as f32 / 10.0)
+        }
+    }
+}